@@ -1,15 +1,17 @@
 use anyhow::bail;
 use clap::{Args, ValueEnum};
-use module_parser::{CargoTomlDependencies, CargoTomlDependency, ConfigModuleMetadata};
+use module_parser::{Capability, CargoTomlDependencies, CargoTomlDependency, ConfigModuleMetadata};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value, json};
 use std::collections::{BTreeMap, HashMap};
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 /// Main application configuration with strongly-typed global sections
 /// and a flexible per-module configuration bag.
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct AppConfig {
     /// Core server configuration.
     pub server: ServerConfig,
@@ -35,7 +37,12 @@ pub struct AppConfig {
 }
 
 impl AppConfig {
-    pub fn create_dependencies(self) -> anyhow::Result<CargoTomlDependencies> {
+    /// `config_path` is the config the user passed, which the fix commands
+    /// of unmet capability requirements edit.
+    pub fn create_dependencies(self, config_path: &Path) -> anyhow::Result<CargoTomlDependencies> {
+        self.validate_module_graph()?;
+        self.validate_capabilities(config_path)?;
+
         let mut dependencies = CargoTomlDependencies::new();
         for (name, module) in self.modules {
             if matches!(
@@ -72,6 +79,268 @@ impl AppConfig {
 
         Ok(dependencies)
     }
+
+    /// Checks that every module's declared `deps` is enabled in this config and
+    /// that the dependencies don't form a cycle. All problems are reported at once.
+    pub fn validate_module_graph(&self) -> anyhow::Result<()> {
+        let issues = self.module_graph_issues();
+        if issues.is_empty() {
+            return Ok(());
+        }
+        bail!(
+            "invalid module dependency graph:\n{}",
+            issues
+                .iter()
+                .map(|issue| format!("  - {issue}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[must_use]
+    pub fn module_graph_issues(&self) -> Vec<ModuleGraphIssue> {
+        let mut issues = Vec::new();
+        for (name, deps) in self.module_deps() {
+            for dep in deps {
+                if !self.modules.contains_key(dep) {
+                    issues.push(ModuleGraphIssue {
+                        module: name.to_owned(),
+                        message: format!(
+                            "module '{name}' depends on '{dep}', which is not enabled in the config"
+                        ),
+                    });
+                }
+            }
+        }
+        for cycle in self.module_dependency_cycles() {
+            issues.push(ModuleGraphIssue {
+                module: cycle.first().cloned().unwrap_or_default(),
+                message: format!("dependency cycle: {}", cycle.join(" -> ")),
+            });
+        }
+        issues
+    }
+
+    fn module_deps(&self) -> BTreeMap<&str, Vec<&str>> {
+        self.modules
+            .iter()
+            .map(|(name, module)| {
+                let deps = module
+                    .metadata
+                    .as_ref()
+                    .map(|metadata| metadata.deps.iter().map(String::as_str).collect())
+                    .unwrap_or_default();
+                (name.as_str(), deps)
+            })
+            .collect()
+    }
+
+    /// Returns the cycle closed by each back edge of a depth-first walk, each
+    /// one closed on its first module (`a -> b -> a`). A cyclic graph yields at
+    /// least one cycle, but not necessarily every elementary one. Edges to
+    /// modules that aren't enabled are ignored here; they're reported as
+    /// missing instead.
+    fn module_dependency_cycles(&self) -> Vec<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Mark {
+            Unvisited,
+            InProgress,
+            Done,
+        }
+
+        fn visit<'a>(
+            node: &'a str,
+            graph: &BTreeMap<&'a str, Vec<&'a str>>,
+            marks: &mut BTreeMap<&'a str, Mark>,
+            stack: &mut Vec<&'a str>,
+            cycles: &mut Vec<Vec<String>>,
+        ) {
+            marks.insert(node, Mark::InProgress);
+            stack.push(node);
+            for &dep in graph.get(node).into_iter().flatten() {
+                match marks.get(dep).copied() {
+                    Some(Mark::Unvisited) => visit(dep, graph, marks, stack, cycles),
+                    Some(Mark::InProgress) => {
+                        if let Some(start) = stack.iter().position(|n| *n == dep) {
+                            let mut cycle: Vec<String> =
+                                stack[start..].iter().map(|n| (*n).to_owned()).collect();
+                            cycle.push(dep.to_owned());
+                            cycles.push(cycle);
+                        }
+                    }
+                    Some(Mark::Done) | None => {}
+                }
+            }
+            stack.pop();
+            marks.insert(node, Mark::Done);
+        }
+
+        let graph = self.module_deps();
+        let mut marks: BTreeMap<&str, Mark> =
+            graph.keys().map(|name| (*name, Mark::Unvisited)).collect();
+        let mut cycles = Vec::new();
+        for &name in graph.keys() {
+            if marks.get(name) == Some(&Mark::Unvisited) {
+                visit(name, &graph, &mut marks, &mut Vec::new(), &mut cycles);
+            }
+        }
+        cycles
+    }
+
+    /// Checks the cross-module rules implied by module capabilities, see
+    /// [`AppConfig::capability_issues`]. The fix commands edit `config_path`.
+    pub fn validate_capabilities(&self, config_path: &Path) -> anyhow::Result<()> {
+        let issues = self.capability_issues();
+        if issues.is_empty() {
+            return Ok(());
+        }
+        bail!(
+            "unmet module capability requirements:\n{}",
+            issues
+                .iter()
+                .map(|issue| format!(
+                    "  - {issue}\n    fix: {}",
+                    issue.fix_command(&config_path.display().to_string())
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    /// A `rest` module needs an enabled `rest_host`, a `grpc` module needs a
+    /// `grpc_hub`, and a `db` module needs either its own `database` block or
+    /// a global `database.servers` entry.
+    #[must_use]
+    pub fn capability_issues(&self) -> Vec<CapabilityIssue> {
+        let has_servers = self
+            .database
+            .as_ref()
+            .is_some_and(|database| !database.servers.is_empty());
+
+        let mut issues = Vec::new();
+        for (name, module) in &self.modules {
+            let capabilities = module_capabilities(module);
+            for provider in CAPABILITY_PROVIDERS {
+                if capabilities.contains(&provider.required_by) && !self.is_provided(provider) {
+                    issues.push(CapabilityIssue {
+                        module: name.clone(),
+                        message: format!(
+                            "module '{name}' has the '{}' capability, but no enabled module provides '{}'",
+                            provider.required_by, provider.capability
+                        ),
+                        fix: format!(
+                            "config mod add {} --package {} --module-version <VERSION>",
+                            provider.module, provider.package
+                        ),
+                    });
+                }
+            }
+            if capabilities.contains(&Capability::Db) && module.database.is_none() && !has_servers {
+                issues.push(CapabilityIssue {
+                    module: name.clone(),
+                    message: format!(
+                        "module '{name}' has the 'db' capability, but it has no `database` block and no `database.servers` entry exists"
+                    ),
+                    fix: format!("config mod db add {name} --engine sqlite --sqlite-file {name}.db"),
+                });
+            }
+        }
+        issues
+    }
+
+    /// The enabled modules providing `capability`, by their capabilities or, for
+    /// registry modules without local metadata, by the well-known provider name.
+    pub fn providers(
+        &self,
+        capability: &Capability,
+    ) -> impl Iterator<Item = (&str, &ModuleConfig)> {
+        let well_known = CAPABILITY_PROVIDERS
+            .iter()
+            .find(|provider| &provider.capability == capability)
+            .map(|provider| provider.module);
+        self.modules
+            .iter()
+            .filter(move |(name, module)| {
+                well_known == Some(name.as_str())
+                    || module_capabilities(module).contains(capability)
+            })
+            .map(|(name, module)| (name.as_str(), module))
+    }
+
+    /// Registry modules enabled without local metadata have no capabilities to
+    /// inspect, so the well-known provider is also recognized by name.
+    fn is_provided(&self, provider: &CapabilityProvider) -> bool {
+        self.modules.iter().any(|(name, module)| {
+            name == provider.module || module_capabilities(module).contains(&provider.capability)
+        })
+    }
+}
+
+fn module_capabilities(module: &ModuleConfig) -> &[Capability] {
+    module
+        .metadata
+        .as_ref()
+        .map(|metadata| metadata.capabilities.as_slice())
+        .unwrap_or_default()
+}
+
+/// A capability that must be provided by another enabled module, along with
+/// the system module that usually provides it.
+struct CapabilityProvider {
+    required_by: Capability,
+    capability: Capability,
+    module: &'static str,
+    package: &'static str,
+}
+
+const CAPABILITY_PROVIDERS: &[CapabilityProvider] = &[
+    CapabilityProvider {
+        required_by: Capability::Rest,
+        capability: Capability::RestHost,
+        module: "api-gateway",
+        package: "cf-api-gateway",
+    },
+    CapabilityProvider {
+        required_by: Capability::Grpc,
+        capability: Capability::GrpcHub,
+        module: "grpc-hub",
+        package: "cf-grpc-hub",
+    },
+];
+
+/// An unmet capability requirement with the CLI arguments that fix it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapabilityIssue {
+    pub module: String,
+    pub message: String,
+    pub fix: String,
+}
+
+impl CapabilityIssue {
+    #[must_use]
+    pub fn fix_command(&self, config: &str) -> String {
+        format!("cargo cyberfabric {} -c {config}", self.fix)
+    }
+}
+
+impl fmt::Display for CapabilityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A problem found in the module dependency graph, attributed to the module
+/// where it was detected (the first module of a cycle).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleGraphIssue {
+    pub module: String,
+    pub message: String,
+}
+
+impl fmt::Display for ModuleGraphIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
 impl Default for AppConfig {
@@ -88,7 +357,7 @@ impl Default for AppConfig {
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ServerConfig {
     #[serde(default = "default_home_dir")]
     pub home_dir: PathBuf,
@@ -124,7 +393,7 @@ pub fn default_logging_config() -> LoggingConfig {
 }
 
 /// Small typed view to parse each module entry.
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ModuleConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub database: Option<DbConnConfig>,
@@ -136,6 +405,41 @@ pub struct ModuleConfig {
     pub metadata: Option<ConfigModuleMetadata>,
 }
 
+/// Where a module listens, as read from its config.
+pub struct ListenAddress<'a> {
+    /// The setting the address came from.
+    pub key: &'static str,
+    /// `None` when only a `port` is configured.
+    pub host: Option<&'a str>,
+    pub port: u16,
+}
+
+impl ModuleConfig {
+    /// The module's `bind_addr`, `listen_addr` or `addr` as `host:port`, or
+    /// else its `port` setting.
+    pub fn listen_address(&self) -> Option<ListenAddress<'_>> {
+        ["bind_addr", "listen_addr", "addr"]
+            .into_iter()
+            .find_map(|key| {
+                let address = self.config.get(key)?.as_str()?;
+                let (host, port) = address.rsplit_once(':')?;
+                Some(ListenAddress {
+                    key,
+                    host: Some(host),
+                    port: port.parse().ok()?,
+                })
+            })
+            .or_else(|| {
+                let port = self.config.get("port")?.as_u64()?;
+                Some(ListenAddress {
+                    key: "port",
+                    host: None,
+                    port: u16::try_from(port).ok()?,
+                })
+            })
+    }
+}
+
 impl Default for ModuleConfig {
     fn default() -> Self {
         Self {
@@ -148,7 +452,7 @@ impl Default for ModuleConfig {
 }
 
 /// Runtime configuration for a module (local vs out-of-process).
-#[derive(Clone, Deserialize, Serialize, Default)]
+#[derive(Clone, Deserialize, Serialize, Default, JsonSchema)]
 pub struct ModuleRuntime {
     #[serde(default, rename = "type")]
     pub mod_type: RuntimeKind,
@@ -158,7 +462,7 @@ pub struct ModuleRuntime {
 }
 
 /// Execution configuration for out-of-process modules.
-#[derive(Clone, Deserialize, Serialize, Default)]
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize, Default, JsonSchema)]
 pub struct ExecutionConfig {
     /// Path to the executable. Supports absolute paths or `~` expansion.
     pub executable_path: String,
@@ -174,7 +478,7 @@ pub struct ExecutionConfig {
 }
 
 /// Module runtime kind.
-#[derive(Clone, Default, Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum RuntimeKind {
     #[default]
@@ -191,7 +495,7 @@ fn default_module_config() -> Value {
 }
 
 /// Global database configuration with server-based DBs.
-#[derive(Clone, Deserialize, Serialize, Default)]
+#[derive(Clone, Deserialize, Serialize, Default, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct GlobalDatabaseConfig {
     /// Server-based DBs (postgres/mysql/sqlite/etc.), keyed by server name.
@@ -203,7 +507,7 @@ pub struct GlobalDatabaseConfig {
 }
 
 /// Reusable DB connection config for both global servers and modules.
-#[derive(Clone, Deserialize, Serialize, Default, Args)]
+#[derive(Clone, Deserialize, Serialize, Default, Args, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct DbConnConfig {
     /// Explicit database engine for this connection.
@@ -252,7 +556,7 @@ pub struct DbConnConfig {
 }
 
 /// Serializable engine selector for configuration.
-#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ValueEnum)]
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ValueEnum, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum DbEngineCfg {
     Postgres,
@@ -261,7 +565,7 @@ pub enum DbEngineCfg {
 }
 
 /// Connection pool configuration.
-#[derive(Clone, Deserialize, Serialize, Default, Args)]
+#[derive(Clone, Deserialize, Serialize, Default, Args, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct PoolCfg {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -414,7 +718,7 @@ pub type VendorConfig = HashMap<String, serde_json::Value>;
 
 /// Top-level OpenTelemetry configuration grouping resource identity,
 /// a shared default exporter, tracing settings and metrics settings.
-#[derive(Clone, Deserialize, Serialize, Default)]
+#[derive(Clone, Deserialize, Serialize, Default, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct OpenTelemetryConfig {
     #[serde(default)]
@@ -429,7 +733,7 @@ pub struct OpenTelemetryConfig {
 }
 
 /// OpenTelemetry resource identity — attached to all traces and metrics.
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct OpenTelemetryResource {
     /// Logical service name.
@@ -455,7 +759,7 @@ impl Default for OpenTelemetryResource {
 }
 
 /// Tracing configuration for OpenTelemetry distributed tracing.
-#[derive(Clone, Deserialize, Serialize, Default)]
+#[derive(Clone, Deserialize, Serialize, Default, JsonSchema)]
 pub struct TracingConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -472,7 +776,7 @@ pub struct TracingConfig {
 }
 
 /// Metrics configuration for OpenTelemetry metrics collection.
-#[derive(Clone, Deserialize, Serialize, Default)]
+#[derive(Clone, Deserialize, Serialize, Default, JsonSchema)]
 pub struct MetricsConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -482,7 +786,7 @@ pub struct MetricsConfig {
     pub cardinality_limit: Option<usize>,
 }
 
-#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[derive(Clone, Copy, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExporterKind {
     #[default]
@@ -490,7 +794,7 @@ pub enum ExporterKind {
     OtlpHttp,
 }
 
-#[derive(Clone, Default, Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct Exporter {
     #[serde(default)]
     pub kind: ExporterKind,
@@ -502,7 +806,7 @@ pub struct Exporter {
     pub timeout_ms: Option<u64>,
 }
 
-#[derive(Clone, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Sampler {
     ParentBasedAlwaysOn {},
@@ -514,13 +818,13 @@ pub enum Sampler {
     AlwaysOff {},
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct Propagation {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub w3c_trace_context: Option<bool>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct HttpOpts {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub inject_request_id_header: Option<String>,
@@ -528,8 +832,165 @@ pub struct HttpOpts {
     pub record_headers: Option<Vec<String>>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct LogsCorrelation {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub inject_trace_ids_into_logs: Option<bool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AppConfig, DbConnConfig, GlobalDatabaseConfig, ModuleConfig};
+    use module_parser::{Capability, ConfigModuleMetadata};
+    use std::path::Path;
+
+    fn config_with_deps(modules: &[(&str, &[&str])]) -> AppConfig {
+        let mut config = AppConfig::default();
+        for (name, deps) in modules {
+            config.modules.insert(
+                (*name).to_owned(),
+                ModuleConfig {
+                    metadata: Some(ConfigModuleMetadata {
+                        package: Some(format!("cf-{name}")),
+                        deps: deps.iter().map(|dep| (*dep).to_owned()).collect(),
+                        ..ConfigModuleMetadata::default()
+                    }),
+                    ..ModuleConfig::default()
+                },
+            );
+        }
+        config
+    }
+
+    #[test]
+    fn module_graph_accepts_enabled_acyclic_deps() {
+        let config = config_with_deps(&[
+            ("api-gateway", &[]),
+            ("demo", &["api-gateway", "authz"]),
+            ("authz", &["api-gateway"]),
+        ]);
+
+        assert!(config.module_graph_issues().is_empty());
+        config
+            .validate_module_graph()
+            .expect("graph should be valid");
+    }
+
+    fn issue_messages(config: &AppConfig) -> Vec<String> {
+        config
+            .module_graph_issues()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn module_graph_reports_missing_deps() {
+        let config = config_with_deps(&[("demo", &["authz", "tenant-resolver"])]);
+
+        assert_eq!(
+            issue_messages(&config),
+            vec![
+                "module 'demo' depends on 'authz', which is not enabled in the config",
+                "module 'demo' depends on 'tenant-resolver', which is not enabled in the config",
+            ]
+        );
+        assert!(
+            config
+                .module_graph_issues()
+                .iter()
+                .all(|issue| issue.module == "demo")
+        );
+    }
+
+    #[test]
+    fn module_graph_reports_cycle_path() {
+        let config =
+            config_with_deps(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"]), ("d", &["d"])]);
+
+        assert_eq!(
+            issue_messages(&config),
+            vec![
+                "dependency cycle: a -> b -> c -> a",
+                "dependency cycle: d -> d"
+            ]
+        );
+    }
+
+    fn with_capabilities(config: &mut AppConfig, module: &str, capabilities: Vec<Capability>) {
+        let metadata = config
+            .modules
+            .entry(module.to_owned())
+            .or_default()
+            .metadata
+            .get_or_insert_with(ConfigModuleMetadata::default);
+        metadata.capabilities = capabilities;
+    }
+
+    #[test]
+    fn capability_issues_require_hosts_and_database() {
+        let mut config = config_with_deps(&[("users", &[]), ("events", &[])]);
+        with_capabilities(&mut config, "users", vec![Capability::Rest, Capability::Db]);
+        with_capabilities(&mut config, "events", vec![Capability::Grpc]);
+
+        let issues = config.capability_issues();
+        let fixes: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.module.as_str(), issue.fix_command("app.yml")))
+            .collect();
+        assert_eq!(
+            fixes,
+            vec![
+                (
+                    "events",
+                    "cargo cyberfabric config mod add grpc-hub --package cf-grpc-hub --module-version <VERSION> -c app.yml".to_owned()
+                ),
+                (
+                    "users",
+                    "cargo cyberfabric config mod add api-gateway --package cf-api-gateway --module-version <VERSION> -c app.yml".to_owned()
+                ),
+                (
+                    "users",
+                    "cargo cyberfabric config mod db add users --engine sqlite --sqlite-file users.db -c app.yml".to_owned()
+                ),
+            ]
+        );
+
+        let err = config
+            .validate_capabilities(Path::new("config/app.yml"))
+            .expect_err("unmet capabilities should fail");
+        assert!(err.to_string().contains(
+            "fix: cargo cyberfabric config mod add grpc-hub --package cf-grpc-hub --module-version <VERSION> -c config/app.yml"
+        ));
+    }
+
+    #[test]
+    fn capability_providers_satisfy_requirements() {
+        let mut config = config_with_deps(&[("users", &[]), ("gateway", &[]), ("grpc-hub", &[])]);
+        with_capabilities(
+            &mut config,
+            "users",
+            vec![Capability::Rest, Capability::Grpc, Capability::Db],
+        );
+        with_capabilities(&mut config, "gateway", vec![Capability::RestHost]);
+        config.database = Some(GlobalDatabaseConfig {
+            servers: [("main".to_owned(), DbConnConfig::default())].into(),
+            auto_provision: None,
+        });
+
+        assert!(config.capability_issues().is_empty());
+        config
+            .validate_capabilities(Path::new("config.yml"))
+            .expect("capabilities should be satisfied");
+    }
+
+    #[test]
+    fn create_dependencies_fails_on_invalid_graph() {
+        let config = config_with_deps(&[("demo", &["authz"])]);
+
+        let Err(err) = config.create_dependencies(Path::new("config.yml")) else {
+            panic!("missing dependency should fail");
+        };
+        assert!(err.to_string().contains("depends on 'authz'"));
+    }
+}
@@ -10,9 +10,12 @@ pub struct BuildArgs {
 
 impl BuildArgs {
     pub fn run(&self) -> anyhow::Result<()> {
-        let (config_path, project_name) = self.build_run_args.resolve_config_and_name()?;
+        let (layers, project_name) = self.build_run_args.resolve_config_and_name()?;
+        let flags = self.build_run_args.flags()?;
+        let vars = self.build_run_args.env.vars()?;
+        let config_path = layers.materialize(&project_name, vars.as_ref())?;
 
-        let dependencies = common::get_config(&config_path)?.create_dependencies()?;
+        let dependencies = common::get_config(&config_path)?.create_dependencies(layers.last())?;
         common::generate_server_structure(&project_name, &dependencies)?;
 
         let cargo_dir = common::generated_project_dir(&project_name)?;
@@ -20,9 +23,9 @@ impl BuildArgs {
             "build",
             &cargo_dir,
             &config_path,
-            self.build_run_args.otel,
-            self.build_run_args.fips,
-            self.build_run_args.release,
+            flags.otel,
+            flags.fips,
+            flags.release,
         )?
         .status()
         .context("failed to run cargo build")?;
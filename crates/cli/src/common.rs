@@ -1,10 +1,12 @@
 use crate::app_config::AppConfig;
-use crate::config::validate_name;
-use anyhow::Context;
+use crate::config::env::{EnvVars, interpolate};
+use crate::config::{overlay, schema, validate_name};
+use crate::settings::{BuildDefaults, ProjectSettings, SETTINGS_FILE};
+use anyhow::{Context, bail};
 use clap::{Args, ValueEnum};
 use module_parser::{
-    CargoToml, CargoTomlDependencies, CargoTomlDependency, ConfigModuleMetadata, Package,
-    get_dependencies, get_module_name_from_crate,
+    CargoToml, CargoTomlDependencies, CargoTomlDependency, ConfigModule, ConfigModuleMetadata,
+    Package, get_dependencies, get_module_name_from_crate,
 };
 use std::collections::{BTreeSet, HashMap};
 use std::env;
@@ -19,9 +21,9 @@ pub struct PathConfigArgs {
     /// Path to the module workspace root
     #[arg(short = 'p', long, value_parser = parse_and_chdir)]
     pub path: Option<PathBuf>,
-    /// Path to the config file
+    /// Path to the config file, defaults to `config` of the project settings
     #[arg(short = 'c', long)]
-    pub config: PathBuf,
+    pub config: Option<PathBuf>,
 }
 
 pub fn parse_and_chdir(s: &str) -> Result<PathBuf, String> {
@@ -38,13 +40,169 @@ pub fn parse_and_chdir(s: &str) -> Result<PathBuf, String> {
 }
 
 impl PathConfigArgs {
-    pub fn resolve_config(&self) -> anyhow::Result<PathBuf> {
+    /// The `-c` path, or the project settings default when it's omitted.
+    pub fn config_path(&self) -> anyhow::Result<PathBuf> {
         self.config
+            .as_ref()
+            .map_or_else(project_config_path, |config| Ok(config.clone()))
+    }
+
+    pub fn resolve_config(&self) -> anyhow::Result<PathBuf> {
+        self.config_path()?
             .canonicalize()
             .context("can't canonicalize config")
     }
 }
 
+/// Config arguments of the commands that generate a server: the config can be
+/// a base file plus overlays, merged before generation.
+#[derive(Args)]
+pub struct LayeredConfigArgs {
+    /// Path to the module workspace root
+    #[arg(short = 'p', long, value_parser = parse_and_chdir)]
+    pub path: Option<PathBuf>,
+    /// Path to the config file, defaults to `config` of the project settings.
+    /// Repeat to merge overlays on top of the first file, in order
+    #[arg(short = 'c', long)]
+    pub config: Vec<PathBuf>,
+    /// Merge the `<profile>` config found next to the base config on top of it
+    #[arg(long)]
+    pub profile: Option<String>,
+}
+
+impl LayeredConfigArgs {
+    pub fn resolve_layers(&self) -> anyhow::Result<ConfigLayers> {
+        let mut layers = if self.config.is_empty() {
+            vec![project_config_path()?]
+        } else {
+            self.config.clone()
+        };
+        if let Some(profile) = &self.profile {
+            validate_name(profile, "profile")?;
+            let base = &layers[0];
+            let extension = base
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("yml");
+            let overlay = base
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(format!("{profile}.{extension}"));
+            if !overlay.is_file() {
+                bail!(
+                    "profile '{profile}' not found: expected {}",
+                    overlay.display()
+                );
+            }
+            layers.push(overlay);
+        }
+        let layers = layers
+            .iter()
+            .map(|layer| {
+                layer
+                    .canonicalize()
+                    .with_context(|| format!("can't canonicalize config {}", layer.display()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(ConfigLayers { layers })
+    }
+}
+
+/// The config files of a generated server, base first.
+#[derive(Clone, Debug)]
+pub struct ConfigLayers {
+    layers: Vec<PathBuf>,
+}
+
+impl ConfigLayers {
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.layers
+    }
+
+    /// The most specific layer, which names the generated server.
+    pub fn last(&self) -> &Path {
+        self.layers
+            .last()
+            .map_or_else(|| Path::new(""), PathBuf::as_path)
+    }
+
+    /// Path of the config handed to the server: the file itself for a single
+    /// layer, otherwise the merged layers written into the generated project.
+    /// With `vars`, the placeholders are expanded and the result is always
+    /// written into the generated project.
+    pub fn materialize(
+        &self,
+        project_name: &str,
+        vars: Option<&EnvVars>,
+    ) -> anyhow::Result<PathBuf> {
+        if let ([single], None) = (self.layers.as_slice(), vars) {
+            return Ok(single.clone());
+        }
+        self.materialize_with(project_name, vars, |_| Ok(()))
+            .map(|(path, ())| path)
+    }
+
+    /// Like [`Self::materialize`], but always writes the merged layers, letting
+    /// `edit` adjust them first.
+    pub fn materialize_with<T>(
+        &self,
+        project_name: &str,
+        vars: Option<&EnvVars>,
+        edit: impl FnOnce(&mut serde_json::Value) -> anyhow::Result<T>,
+    ) -> anyhow::Result<(PathBuf, T)> {
+        let extension = self.layers[0]
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("yml");
+        let merged_path = generated_project_dir(project_name)?.join(format!("config.{extension}"));
+        let mut merged = overlay::merge_layers(&self.layers)?;
+        if let Some(vars) = vars {
+            interpolate(&mut merged, vars)?;
+        }
+        let edited = edit(&mut merged)?;
+        overlay::write_merged(&merged, &merged_path)?;
+        Ok((merged_path, edited))
+    }
+
+    /// Fails when a placeholder of the merged layers doesn't resolve.
+    pub fn check_env(&self, vars: &EnvVars) -> anyhow::Result<()> {
+        interpolate(&mut overlay::merge_layers(&self.layers)?, vars)
+    }
+}
+
+/// Opt-in `${VAR}` expansion of the config.
+#[derive(Args)]
+pub struct EnvArgs {
+    /// Expand `${VAR}` and `${VAR:-default}` in the config, failing on unset variables
+    #[arg(long)]
+    pub interpolate: bool,
+    /// Dotenv file with variables for the expansion; implies --interpolate
+    #[arg(long, value_name = "PATH")]
+    pub env_file: Option<PathBuf>,
+}
+
+impl EnvArgs {
+    /// The variables to expand with, or `None` when expansion is off.
+    pub fn vars(&self) -> anyhow::Result<Option<EnvVars>> {
+        if !self.interpolate && self.env_file.is_none() {
+            return Ok(None);
+        }
+        EnvVars::load(self.env_file.as_deref()).map(Some)
+    }
+}
+
+/// The `config` of the project settings, for commands run without `-c`.
+fn project_config_path() -> anyhow::Result<PathBuf> {
+    ProjectSettings::load(&workspace_root()?)?
+        .config
+        .with_context(|| {
+            format!(
+                "no config file given: pass -c/--config or set `config` in \
+                 [workspace.metadata.cyberfabric] or {SETTINGS_FILE}"
+            )
+        })
+}
+
 pub fn workspace_root() -> anyhow::Result<PathBuf> {
     env::current_dir().context("can't determine current working directory")
 }
@@ -52,22 +210,33 @@ pub fn workspace_root() -> anyhow::Result<PathBuf> {
 #[derive(Args)]
 pub struct BuildRunArgs {
     #[command(flatten)]
-    pub path_config: PathConfigArgs,
+    pub path_config: LayeredConfigArgs,
     /// Use OpenTelemetry tracing
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_otel")]
     pub otel: bool,
+    /// Build without OpenTelemetry tracing, even when the project settings enable it
+    #[arg(long, overrides_with = "otel")]
+    pub no_otel: bool,
     /// Enable FIPS mode
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_fips")]
     pub fips: bool,
+    /// Build without FIPS mode, even when the project settings enable it
+    #[arg(long, overrides_with = "fips")]
+    pub no_fips: bool,
     /// Build/run in release mode
-    #[arg(short = 'r', long)]
+    #[arg(short = 'r', long, overrides_with = "no_release")]
     pub release: bool,
+    /// Build/run in debug mode, even when the project settings enable release mode
+    #[arg(long, overrides_with = "release")]
+    pub no_release: bool,
     /// Remove Cargo.lock at the start of the execution
     #[arg(long)]
     pub clean: bool,
     /// Override the generated server and binary name
     #[arg(long)]
     pub name: Option<String>,
+    #[command(flatten)]
+    pub env: EnvArgs,
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
@@ -91,21 +260,52 @@ impl Display for Registry {
     }
 }
 
+/// Effective `--otel`, `--fips` and `--release` once the project settings
+/// are applied.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BuildFlags {
+    pub otel: bool,
+    pub fips: bool,
+    pub release: bool,
+}
+
 impl BuildRunArgs {
-    pub fn resolve_config_and_name(&self) -> anyhow::Result<(PathBuf, String)> {
-        let config_path = self.path_config.resolve_config()?;
-        let project_name = resolve_generated_project_name(&config_path, self.name.as_deref())?;
+    /// Each flag comes from the command line, then the project settings, and is
+    /// off otherwise.
+    pub fn flags(&self) -> anyhow::Result<BuildFlags> {
+        let defaults = ProjectSettings::load(&workspace_root()?)?.build;
+        Ok(self.flags_over(&defaults))
+    }
+
+    fn flags_over(&self, defaults: &BuildDefaults) -> BuildFlags {
+        let flag = |on: bool, off: bool, default: Option<bool>| {
+            if on || off {
+                on
+            } else {
+                default.unwrap_or(false)
+            }
+        };
+        BuildFlags {
+            otel: flag(self.otel, self.no_otel, defaults.otel),
+            fips: flag(self.fips, self.no_fips, defaults.fips),
+            release: flag(self.release, self.no_release, defaults.release),
+        }
+    }
+
+    pub fn resolve_config_and_name(&self) -> anyhow::Result<(ConfigLayers, String)> {
+        let layers = self.path_config.resolve_layers()?;
+        let project_name = resolve_generated_project_name(layers.last(), self.name.as_deref())?;
         if self.clean {
             remove_from_file_structure(&project_name, "Cargo.lock")?;
         }
 
-        Ok((config_path, project_name))
+        Ok((layers, project_name))
     }
 }
 
 pub const BASE_PATH: &str = ".cyberfabric";
 
-const CONFIG_PATH_ENV_VAR: &str = "CF_CLI_CONFIG";
+pub const CONFIG_PATH_ENV_VAR: &str = "CF_CLI_CONFIG";
 
 const CARGO_CONFIG_TOML: &str = r#"[build]
 target-dir = "../../target"
@@ -165,24 +365,46 @@ pub fn cargo_command(
 
 pub fn get_config(config_path: &Path) -> anyhow::Result<AppConfig> {
     let mut config = get_config_from_path(config_path)?;
-    let mut members = get_module_name_from_crate()?;
+    let members = get_module_name_from_crate()?;
+    let schemas = schema::load_module_schemas(&members)?;
+
+    for module in apply_local_module_metadata(&mut config, members) {
+        eprintln!(
+            "info: config module '{module}' not found locally, retrieving it from the registry"
+        );
+    }
+
+    let issues = schema::module_config_issues(&config, &schemas)?;
+    if !issues.is_empty() {
+        let details: Vec<_> = issues.iter().map(ToString::to_string).collect();
+        bail!(
+            "module config doesn't match the module's schema:\n  {}",
+            details.join("\n  ")
+        );
+    }
+
+    Ok(config)
+}
 
-    config.modules.iter_mut().for_each(|module| {
-        if let Some(module_metadata) = members.remove(module.0.as_str()) {
-            let config_metadata = std::mem::take(&mut module.1.metadata).unwrap_or_default();
-            module.1.metadata = Some(merge_module_metadata(
+/// Merges the metadata of workspace modules into the matching config entries
+/// and returns the names of the config modules that aren't in the workspace.
+pub fn apply_local_module_metadata(
+    config: &mut AppConfig,
+    mut members: HashMap<String, ConfigModule>,
+) -> Vec<String> {
+    let mut not_local = Vec::new();
+    for (name, module) in &mut config.modules {
+        if let Some(module_metadata) = members.remove(name.as_str()) {
+            let config_metadata = std::mem::take(&mut module.metadata).unwrap_or_default();
+            module.metadata = Some(merge_module_metadata(
                 config_metadata,
                 module_metadata.metadata,
             ));
         } else {
-            eprintln!(
-                "info: config module '{}' not found locally, retrieving it from the registry",
-                module.0
-            );
+            not_local.push(name.clone());
         }
-    });
-
-    Ok(config)
+    }
+    not_local
 }
 
 fn get_config_from_path(path: &Path) -> anyhow::Result<AppConfig> {
@@ -260,10 +482,12 @@ fn create_required_deps() -> anyhow::Result<CargoTomlDependencies> {
     Ok(deps)
 }
 
-pub fn generate_server_structure(
-    project_name: &str,
+/// The `[dependencies]` of the generated server for the module dependencies
+/// of a config: workspace paths made relative to the server, plus the crates
+/// every server needs.
+pub fn server_dependencies(
     current_dependencies: &CargoTomlDependencies,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<CargoTomlDependencies> {
     let workspace = workspace_root()?
         .to_str()
         .context("workspace path is not valid UTF-8")?
@@ -273,12 +497,19 @@ pub fn generate_server_structure(
         .map(|(name, dep)| (name.clone(), make_absolute_paths_relative(dep, &workspace)))
         .collect();
     dependencies.extend(create_required_deps()?);
+    Ok(dependencies)
+}
+
+pub fn generate_server_structure(
+    project_name: &str,
+    current_dependencies: &CargoTomlDependencies,
+) -> anyhow::Result<()> {
     let cargo_toml = CargoToml {
         package: Package {
             name: project_name.to_owned(),
             ..Default::default()
         },
-        dependencies,
+        dependencies: server_dependencies(current_dependencies)?,
         features: FEATURES.clone(),
         ..Default::default()
     };
@@ -404,9 +635,9 @@ fn prepare_cargo_server_main(dependencies: &CargoTomlDependencies) -> String {
 #[cfg(test)]
 mod tests {
     use super::{
-        cargo_command, generate_server_structure, generated_project_dir,
-        make_absolute_paths_relative, merge_module_metadata, prepare_cargo_server_main,
-        resolve_generated_project_name,
+        BuildFlags, BuildRunArgs, LayeredConfigArgs, cargo_command, generate_server_structure,
+        generated_project_dir, make_absolute_paths_relative, merge_module_metadata,
+        prepare_cargo_server_main, resolve_generated_project_name,
     };
     use module_parser::{
         Capability, CargoTomlDependencies, CargoTomlDependency, ConfigModuleMetadata,
@@ -419,6 +650,53 @@ mod tests {
 
     static CURRENT_DIR_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
 
+    #[test]
+    fn command_line_flags_override_project_build_defaults() {
+        #[derive(clap::Parser)]
+        struct BuildCli {
+            #[command(flatten)]
+            args: BuildRunArgs,
+        }
+        let flags = |cli: &[&str]| {
+            let defaults = crate::settings::BuildDefaults {
+                otel: Some(true),
+                fips: None,
+                release: Some(true),
+            };
+            <BuildCli as clap::Parser>::try_parse_from(
+                std::iter::once("build").chain(cli.iter().copied()),
+            )
+            .expect("args")
+            .args
+            .flags_over(&defaults)
+        };
+
+        assert_eq!(
+            flags(&[]),
+            BuildFlags {
+                otel: true,
+                fips: false,
+                release: true
+            }
+        );
+        assert_eq!(
+            flags(&["--no-otel", "--fips", "--no-release"]),
+            BuildFlags {
+                otel: false,
+                fips: true,
+                release: false
+            }
+        );
+        assert_eq!(
+            flags(&["--no-release", "-r"]),
+            BuildFlags {
+                otel: true,
+                fips: false,
+                release: true
+            }
+        );
+    }
+
     struct CwdRestoreGuard {
         original_dir: std::path::PathBuf,
     }
@@ -496,6 +774,38 @@ path = "src/lib.rs"
         assert_eq!(name, "demo");
     }
 
+    #[test]
+    fn profile_layers_on_top_of_base_config() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        temp_dir.write("config/base.yml", "server: {}\n");
+        temp_dir.write("config/prod.yml", "server: {}\n");
+        let base = temp_dir.path().join("config/base.yml");
+        let args = |profile: &str| LayeredConfigArgs {
+            path: None,
+            config: vec![base.clone()],
+            profile: Some(profile.to_owned()),
+        };
+
+        let layers = args("prod")
+            .resolve_layers()
+            .expect("layers should resolve");
+        let names: Vec<_> = layers
+            .paths()
+            .iter()
+            .filter_map(|layer| layer.file_name())
+            .collect();
+        assert_eq!(names, ["base.yml", "prod.yml"]);
+        assert_eq!(
+            resolve_generated_project_name(layers.last(), None).expect("name"),
+            "prod"
+        );
+
+        let err = args("staging")
+            .resolve_layers()
+            .expect_err("missing profile");
+        assert!(err.to_string().contains("profile 'staging' not found"));
+    }
+
     #[test]
     fn generated_server_main_reads_config_from_env_and_includes_dependencies() {
         let dependencies = CargoTomlDependencies::from([
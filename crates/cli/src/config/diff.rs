@@ -0,0 +1,262 @@
+use super::document::{self, Change, flow};
+use super::load_config;
+use crate::common::{parse_and_chdir, server_dependencies};
+use anyhow::{Context, bail};
+use clap::Args;
+use module_parser::{CargoTomlDependencies, get_module_name_from_crate};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Path to the module workspace root, whose module metadata `--deps` uses
+    #[arg(short = 'p', long, value_parser = parse_and_chdir)]
+    path: Option<PathBuf>,
+    /// Config to compare from; with `--deps` it can also be a generated server's `Cargo.toml`
+    old: PathBuf,
+    /// Config to compare to; with `--deps` it can also be a generated server's `Cargo.toml`
+    new: PathBuf,
+    /// Compare the Cargo dependencies the configs generate instead of the configs
+    #[arg(long)]
+    deps: bool,
+}
+
+impl DiffArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let manifests = is_manifest(&self.old) || is_manifest(&self.new);
+        let (old, new) = if self.deps {
+            (
+                dependencies(&self.old, manifests)?,
+                dependencies(&self.new, manifests)?,
+            )
+        } else if manifests {
+            bail!("a Cargo.toml can only be compared with --deps");
+        } else {
+            (config(&self.old)?, config(&self.new)?)
+        };
+
+        let differences = diff(&old, &new);
+        println!("--- {}", self.old.display());
+        println!("+++ {}", self.new.display());
+        for difference in &differences {
+            println!("{difference}");
+        }
+        match differences.len() {
+            0 => println!("no differences"),
+            count => println!("{count} difference(s)"),
+        }
+        Ok(())
+    }
+}
+
+/// The `[dependencies]` of a generated server's manifest.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    dependencies: CargoTomlDependencies,
+}
+
+fn is_manifest(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "toml")
+}
+
+/// The config with its defaults filled in, so that leaving a setting out and
+/// spelling out its default compare equal.
+fn config(path: &Path) -> anyhow::Result<Value> {
+    serde_json::to_value(load_config(path)?).context("failed to serialize config")
+}
+
+/// The dependencies `build` generates for the config at `path`, as the
+/// generated server declares them when `server` is set, or the ones declared
+/// by the manifest at `path`.
+fn dependencies(path: &Path, server: bool) -> anyhow::Result<Value> {
+    let dependencies = if is_manifest(path) {
+        let manifest = fs::read_to_string(path)
+            .with_context(|| format!("can't read manifest {}", path.display()))?;
+        toml::from_str::<Manifest>(&manifest)
+            .with_context(|| format!("manifest not valid at {}", path.display()))?
+            .dependencies
+    } else {
+        let mut config = load_config(path)?;
+        let members = get_module_name_from_crate()
+            .context("--deps reads the metadata of the workspace modules, run it in the workspace or pass -p")?;
+        crate::common::apply_local_module_metadata(&mut config, members);
+        let dependencies = config
+            .create_dependencies(path)
+            .with_context(|| format!("can't resolve the dependencies of {}", path.display()))?;
+        if server {
+            server_dependencies(&dependencies)?
+        } else {
+            dependencies
+        }
+    };
+    serde_json::to_value(dependencies).context("failed to serialize dependencies")
+}
+
+/// A difference at a dotted path.
+#[derive(Debug, PartialEq, Eq)]
+enum Difference {
+    Added(String, Value),
+    Removed(String, Value),
+    Changed(String, Value, Value),
+    /// Entries added to and removed from a list of scalars, such as features.
+    Items {
+        path: String,
+        added: Vec<Value>,
+        removed: Vec<Value>,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added(path, value) => write!(f, "+ {path}: {}", flow(value)),
+            Self::Removed(path, value) => write!(f, "- {path}: {}", flow(value)),
+            Self::Changed(path, old, new) => {
+                write!(f, "~ {path}: {} -> {}", flow(old), flow(new))
+            }
+            Self::Items {
+                path,
+                added,
+                removed,
+            } => {
+                write!(f, "~ {path}:")?;
+                for item in added {
+                    write!(f, " +{}", flow(item))?;
+                }
+                for item in removed {
+                    write!(f, " -{}", flow(item))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The differences between `old` and `new`, from the entry-by-entry
+/// [`document::diff`] of the two: a `null` counts as a missing entry, lists of
+/// scalars are compared as sets unless only their order changed, and removed
+/// entries come last.
+fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    document::diff(old, new)
+        .into_iter()
+        .filter_map(|change| {
+            let (path, new) = match change {
+                Change::Set(path, value) => (path, value),
+                Change::Unset(path) => (path, Value::Null),
+            };
+            let old = path
+                .iter()
+                .try_fold(old, |node, key| node.get(key))
+                .unwrap_or(&Value::Null);
+            difference(path.join("."), old, new)
+        })
+        .collect()
+}
+
+fn difference(path: String, old: &Value, new: Value) -> Option<Difference> {
+    Some(match (old, &new) {
+        _ if *old == new => return None,
+        (Value::Null, _) => Difference::Added(path, new),
+        (_, Value::Null) => Difference::Removed(path, old.clone()),
+        (Value::Array(old_items), Value::Array(new_items))
+            if is_scalar_list(old_items) && is_scalar_list(new_items) =>
+        {
+            let added: Vec<_> = new_items
+                .iter()
+                .filter(|item| !old_items.contains(item))
+                .cloned()
+                .collect();
+            let removed: Vec<_> = old_items
+                .iter()
+                .filter(|item| !new_items.contains(item))
+                .cloned()
+                .collect();
+            if added.is_empty() && removed.is_empty() {
+                Difference::Changed(path, old.clone(), new)
+            } else {
+                Difference::Items {
+                    path,
+                    added,
+                    removed,
+                }
+            }
+        }
+        _ => Difference::Changed(path, old.clone(), new),
+    })
+}
+
+fn is_scalar_list(items: &[Value]) -> bool {
+    items
+        .iter()
+        .all(|item| !matches!(item, Value::Array(_) | Value::Object(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+    use crate::app_config::AppConfig;
+    use serde_json::Value;
+
+    fn config(yaml: &str) -> Value {
+        let config: AppConfig = serde_saphyr::from_str(yaml).expect("valid config");
+        serde_json::to_value(config).expect("serializable config")
+    }
+
+    #[test]
+    fn reports_module_metadata_and_database_changes() {
+        let staging = config(
+            "server:
+  home_dir: ~/.cyberfabric
+modules:
+  users:
+    metadata:
+      package: cf-users
+      version: \"1.2\"
+      features: [json, metrics]
+    database:
+      server: main
+  orders:
+    metadata: {package: cf-orders, version: \"0.4\"}
+",
+        );
+        let prod = config(
+            "server:
+  home_dir: ~/.cyberfabric
+modules:
+  users:
+    metadata:
+      package: cf-users
+      version: \"1.3\"
+      features: [json, tracing]
+    database:
+      server: main
+      pool:
+        max_conns: 20
+  billing:
+    metadata: {package: cf-billing, version: \"2.0\"}
+",
+        );
+
+        let lines: Vec<String> = diff(&staging, &prod)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "+ modules.billing: {config: {}, metadata: {package: cf-billing, version: \"2.0\"}}",
+                "+ modules.users.database.pool: {max_conns: 20}",
+                "~ modules.users.metadata.features: +tracing -metrics",
+                "~ modules.users.metadata.version: \"1.2\" -> \"1.3\"",
+                "- modules.orders: {config: {}, metadata: {package: cf-orders, version: \"0.4\"}}",
+            ]
+        );
+        assert!(diff(&staging, &staging).is_empty());
+    }
+}
@@ -0,0 +1,860 @@
+//! Format-preserving edits of YAML config files.
+//!
+//! Edits are spliced into the source text at the spans of the parsed nodes, so
+//! comments, key order, quoting and anchors outside the edited entries stay
+//! byte-identical. New values are rendered in block style and indented like
+//! their siblings; inside a flow collection (`[a, b]`, `{a: 1}`) the changed
+//! entries are rendered in flow style and the others keep their source text.
+
+use super::yaml::{YamlNode, YamlNodeKind, parse_document};
+use anyhow::{Context, bail};
+use saphyr_parser_bw::Marker;
+use serde_json::{Map, Value};
+
+/// A config file's source text, edited in place.
+pub struct ConfigDocument {
+    text: String,
+}
+
+/// A difference between two config trees, as produced by [`diff`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Set(Vec<String>, Value),
+    Unset(Vec<String>),
+}
+
+/// Where a node sits in the document.
+#[derive(Clone, Copy)]
+enum Slot<'a> {
+    Root,
+    /// Value of a block mapping entry with this key.
+    Entry(&'a YamlNode),
+    /// Item of a block sequence.
+    Item,
+}
+
+impl ConfigDocument {
+    #[must_use]
+    pub const fn new(text: String) -> Self {
+        Self { text }
+    }
+
+    #[must_use]
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    pub fn apply(&mut self, changes: &[Change]) -> anyhow::Result<()> {
+        for change in changes {
+            match change {
+                Change::Set(path, value) => self.set(path, value)?,
+                Change::Unset(path) => {
+                    self.unset(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the value at `path`, creating the missing mappings on the way.
+    /// Numeric segments index into sequences, and the index right after the
+    /// last item appends to the sequence.
+    pub fn set<S: AsRef<str>>(&mut self, path: &[S], value: &Value) -> anyhow::Result<()> {
+        let Some(root) = parse(&self.text)? else {
+            let mut rendered = block(&nest(path, value), 0);
+            rendered.push('\n');
+            self.text.push_str(&rendered);
+            return Ok(());
+        };
+        let chain = resolve(&root, path)?;
+        if let Some(flow) = self.flow_ancestor(&chain, path.len()) {
+            let node = chain[flow].1;
+            let mut replaced = to_value(&self.text, node)?;
+            set_in(&mut replaced, &path[flow..], value.clone())?;
+            self.replace_flow(node, &replaced);
+            return Ok(());
+        }
+
+        let depth = chain.len() - 1;
+        let (slot, node) = chain[depth];
+        if depth == path.len() {
+            return self.replace(slot, node, value);
+        }
+        let key = path[depth].as_ref();
+        let nested = nest(&path[depth + 1..], value);
+        match &node.kind {
+            YamlNodeKind::Mapping(entries) if !entries.is_empty() => {
+                self.insert_entry(node, key, &nested);
+                Ok(())
+            }
+            YamlNodeKind::Sequence(items) if !items.is_empty() => {
+                if key.parse::<usize>().ok() != Some(items.len()) {
+                    bail!("index {key} is out of range of '{}'", join(&path[..depth]));
+                }
+                self.append_item(node, &nested);
+                Ok(())
+            }
+            YamlNodeKind::Mapping(_) => self.replace(
+                slot,
+                node,
+                &Value::Object(Map::from_iter([(key.to_owned(), nested)])),
+            ),
+            YamlNodeKind::Sequence(_) if key == "0" => {
+                self.replace(slot, node, &Value::Array(vec![nested]))
+            }
+            YamlNodeKind::Scalar(scalar) if is_null(node, scalar) => {
+                let value = if key == "0" {
+                    Value::Array(vec![nested])
+                } else {
+                    Value::Object(Map::from_iter([(key.to_owned(), nested)]))
+                };
+                self.replace(slot, node, &value)
+            }
+            _ => bail!(
+                "'{}' isn't a mapping, so it can't have a '{key}' entry",
+                join(&path[..depth])
+            ),
+        }
+    }
+
+    /// Removes the entry or sequence item at `path`. Returns `false` when
+    /// there is nothing to remove.
+    pub fn unset<S: AsRef<str>>(&mut self, path: &[S]) -> anyhow::Result<bool> {
+        let Some((last, parent_path)) = path.split_last() else {
+            bail!("can't remove the whole config");
+        };
+        let Some(root) = parse(&self.text)? else {
+            return Ok(false);
+        };
+        let chain = resolve(&root, path)?;
+        if chain.len() <= path.len() {
+            return Ok(false);
+        }
+        if let Some(flow) = self.flow_ancestor(&chain, path.len()) {
+            let node = chain[flow].1;
+            let mut replaced = to_value(&self.text, node)?;
+            remove_in(&mut replaced, &path[flow..]);
+            self.replace_flow(node, &replaced);
+            return Ok(true);
+        }
+
+        let (slot, parent) = chain[parent_path.len()];
+        match &parent.kind {
+            YamlNodeKind::Mapping(entries) => {
+                if entries.len() == 1 {
+                    self.replace(slot, parent, &Value::Object(Map::new()))?;
+                    return Ok(true);
+                }
+                let Some(index) = entries
+                    .iter()
+                    .position(|(key, _)| key.as_str() == Some(last.as_ref()))
+                else {
+                    return Ok(false);
+                };
+                let (key, value) = &entries[index];
+                let start = self.offset(&key.span.start);
+                let end = self.line_end(self.content_end(value).max(self.offset(&key.span.end)));
+                let line_start = self.line_start(start);
+                if self.text[line_start..start].trim().is_empty() {
+                    let start = self.comments_above(line_start, start - line_start);
+                    self.splice(start, end, "");
+                } else if let Some((next, _)) = entries.get(index + 1) {
+                    // The first key of a sequence item, as in `- a: 1`.
+                    let next = self.offset(&next.span.start);
+                    self.splice(start, next, "");
+                } else {
+                    bail!("can't remove '{}' without rewriting its line", join(path));
+                }
+            }
+            YamlNodeKind::Sequence(items) => {
+                if items.len() == 1 {
+                    self.replace(slot, parent, &Value::Array(Vec::new()))?;
+                    return Ok(true);
+                }
+                let Some(item) = last
+                    .as_ref()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.get(i))
+                else {
+                    return Ok(false);
+                };
+                let start = self.line_start(self.offset(&item.span.start));
+                let end = self.line_end(self.content_end(item));
+                self.splice(start, end, "");
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The outermost non-empty flow collection that contains the value at a
+    /// path of `len` segments, which an edit of that value rewrites as a whole.
+    fn flow_ancestor(&self, chain: &[(Slot<'_>, &YamlNode)], len: usize) -> Option<usize> {
+        chain
+            .iter()
+            .take(len)
+            .position(|(_, node)| self.is_flow(node) && !is_empty_collection(node))
+    }
+
+    fn replace_flow(&mut self, node: &YamlNode, value: &Value) {
+        let start = self.offset(&node.span.start);
+        let end = self.content_end(node);
+        let rendered = self.flow_from(node, value);
+        self.splice(start, end, &rendered);
+    }
+
+    /// Flow YAML for `value` that replaces `node`, keeping the source text of
+    /// the entries and items that `value` holds unchanged.
+    fn flow_from(&self, node: &YamlNode, value: &Value) -> String {
+        if to_value(&self.text, node).ok().as_ref() == Some(value) {
+            return self.source(node).to_owned();
+        }
+        match (&node.kind, value) {
+            (YamlNodeKind::Mapping(entries), Value::Object(map)) if self.is_flow(node) => {
+                let kept = entries.iter().filter_map(|(key, child)| {
+                    let new = map.get(key.as_str()?)?;
+                    Some(format!(
+                        "{}: {}",
+                        self.source(key),
+                        self.flow_from(child, new)
+                    ))
+                });
+                let added = map
+                    .iter()
+                    .filter(|(name, _)| {
+                        !entries
+                            .iter()
+                            .any(|(key, _)| key.as_str() == Some(name.as_str()))
+                    })
+                    .map(|(name, new)| format!("{}: {}", scalar(name), flow(new)));
+                format!("{{{}}}", kept.chain(added).collect::<Vec<_>>().join(", "))
+            }
+            (YamlNodeKind::Sequence(items), Value::Array(values)) if self.is_flow(node) => {
+                // Items are matched in order, so removing one keeps the rest.
+                let mut next = 0;
+                let rendered: Vec<_> = values
+                    .iter()
+                    .map(|new| {
+                        let unchanged = items[next.min(items.len())..]
+                            .iter()
+                            .position(|item| to_value(&self.text, item).ok().as_ref() == Some(new));
+                        if let Some(found) = unchanged {
+                            next += found + 1;
+                            return self.source(&items[next - 1]).to_owned();
+                        }
+                        next += 1;
+                        items
+                            .get(next - 1)
+                            .map_or_else(|| flow(new), |item| self.flow_from(item, new))
+                    })
+                    .collect();
+                format!("[{}]", rendered.join(", "))
+            }
+            _ => flow(value),
+        }
+    }
+
+    /// Replaces `node` with `value`, keeping what surrounds it.
+    fn replace(&mut self, slot: Slot<'_>, node: &YamlNode, value: &Value) -> anyhow::Result<()> {
+        if self.is_flow(node)
+            && !is_empty_collection(node)
+            && matches!(value, Value::Array(_) | Value::Object(_))
+        {
+            // A collection written in flow style stays in flow style; empty
+            // placeholders such as `{}` grow into blocks.
+            self.replace_flow(node, value);
+            return Ok(());
+        }
+        let end = self.content_end(node);
+        match slot {
+            Slot::Root => {
+                let start = self.offset(&node.span.start);
+                let rendered = inline(value).unwrap_or_else(|| block(value, 0));
+                self.splice(start, end, &rendered);
+            }
+            Slot::Entry(key) => {
+                let key_end = self.offset(&key.span.end);
+                let colon = self.text[key_end..]
+                    .find(':')
+                    .map(|found| key_end + found + 1)
+                    .context("mapping key without a colon")?;
+                let rendered = inline(value).map_or_else(
+                    || format!("\n{}", block(value, self.column(key) + 2)),
+                    |inline| format!(" {inline}"),
+                );
+                self.splice(colon, end.max(colon), &rendered);
+            }
+            Slot::Item => {
+                let start = self.offset(&node.span.start);
+                let rendered =
+                    inline(value).unwrap_or_else(|| item_block(value, self.column(node)));
+                self.splice(start, end, &rendered);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_entry(&mut self, mapping: &YamlNode, key: &str, value: &Value) {
+        let YamlNodeKind::Mapping(entries) = &mapping.kind else {
+            return;
+        };
+        let Some(((first, _), (last_key, last_value))) = entries.first().zip(entries.last()) else {
+            return;
+        };
+        let indent = self.column(first);
+        let at = self.line_end_before_newline(
+            self.content_end(last_value)
+                .max(self.offset(&last_key.span.end)),
+        );
+        let entry = block(
+            &Value::Object(Map::from_iter([(key.to_owned(), value.clone())])),
+            indent,
+        );
+        self.splice(at, at, &format!("\n{entry}"));
+    }
+
+    fn append_item(&mut self, sequence: &YamlNode, value: &Value) {
+        let YamlNodeKind::Sequence(items) = &sequence.kind else {
+            return;
+        };
+        let Some((first, last)) = items.first().zip(items.last()) else {
+            return;
+        };
+        let first_start = self.offset(&first.span.start);
+        let dash = self.text[self.line_start(first_start)..first_start]
+            .rfind('-')
+            .unwrap_or_default();
+        let at = self.line_end_before_newline(self.content_end(last));
+        let item = inline(value).unwrap_or_else(|| item_block(value, dash + 2));
+        self.splice(at, at, &format!("\n{}- {item}", " ".repeat(dash)));
+    }
+
+    fn splice(&mut self, start: usize, end: usize, with: &str) {
+        self.text.replace_range(start..end, with);
+    }
+
+    /// Byte offset of `marker` in the text.
+    fn offset(&self, marker: &Marker) -> usize {
+        marker.byte_offset().unwrap_or_else(|| {
+            self.text
+                .char_indices()
+                .nth(marker.index())
+                .map_or(self.text.len(), |(offset, _)| offset)
+        })
+    }
+
+    fn column(&self, node: &YamlNode) -> usize {
+        let start = self.offset(&node.span.start);
+        self.text[self.line_start(start)..start].chars().count()
+    }
+
+    /// The source text of `node`.
+    fn source(&self, node: &YamlNode) -> &str {
+        &self.text[self.offset(&node.span.start)..self.content_end(node)]
+    }
+
+    fn is_flow(&self, node: &YamlNode) -> bool {
+        matches!(
+            node.kind,
+            YamlNodeKind::Mapping(_) | YamlNodeKind::Sequence(_)
+        ) && self.text[self.offset(&node.span.start)..].starts_with(['[', '{'])
+    }
+
+    /// Where the source of `node` ends, leaving out the line breaks and
+    /// comments that the parser counts into block nodes.
+    fn content_end(&self, node: &YamlNode) -> usize {
+        let start = self.offset(&node.span.start);
+        let end = match &node.kind {
+            _ if self.is_flow(node) => self.offset(&node.span.end),
+            YamlNodeKind::Mapping(entries) => entries.last().map_or(start, |(key, value)| {
+                self.content_end(value).max(self.offset(&key.span.end))
+            }),
+            YamlNodeKind::Sequence(items) => {
+                items.last().map_or(start, |item| self.content_end(item))
+            }
+            YamlNodeKind::Scalar(_) | YamlNodeKind::Alias => self.offset(&node.span.end),
+        };
+        start + self.text[start..end.max(start)].trim_end().len()
+    }
+
+    /// Extends the line at `line_start` up over the comment lines right above
+    /// it that are indented by `indent`, which describe the entry on that line.
+    fn comments_above(&self, mut line_start: usize, indent: usize) -> usize {
+        while line_start > 0 {
+            let previous = self.line_start(line_start - 1);
+            let line = &self.text[previous..line_start];
+            let trimmed = line.trim_start();
+            if !trimmed.starts_with('#') || line.len() - trimmed.len() != indent {
+                break;
+            }
+            line_start = previous;
+        }
+        line_start
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.text[..offset].rfind('\n').map_or(0, |found| found + 1)
+    }
+
+    /// The start of the next line, or the end of the text.
+    fn line_end(&self, offset: usize) -> usize {
+        self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |found| offset + found + 1)
+    }
+
+    fn line_end_before_newline(&self, offset: usize) -> usize {
+        self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |found| offset + found)
+    }
+}
+
+fn parse(text: &str) -> anyhow::Result<Option<YamlNode>> {
+    parse_document(text).context("config isn't valid YAML")
+}
+
+/// The nodes from the root along `path`, as far as they exist.
+fn resolve<'a, S: AsRef<str>>(
+    root: &'a YamlNode,
+    path: &[S],
+) -> anyhow::Result<Vec<(Slot<'a>, &'a YamlNode)>> {
+    let mut chain = vec![(Slot::Root, root)];
+    let mut node = root;
+    for (depth, segment) in path.iter().enumerate() {
+        let segment = segment.as_ref();
+        let next = match &node.kind {
+            YamlNodeKind::Mapping(entries) => entries.iter().find_map(|(key, value)| {
+                (key.as_str() == Some(segment)).then_some((Slot::Entry(key), value))
+            }),
+            YamlNodeKind::Sequence(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get(index))
+                .map(|item| (Slot::Item, item)),
+            YamlNodeKind::Alias => bail!(
+                "'{}' is a YAML alias, edit its anchor instead",
+                join(&path[..depth])
+            ),
+            YamlNodeKind::Scalar(_) => None,
+        };
+        let Some(next) = next else {
+            break;
+        };
+        chain.push(next);
+        node = next.1;
+    }
+    Ok(chain)
+}
+
+fn to_value(text: &str, node: &YamlNode) -> anyhow::Result<Value> {
+    let start = node.span.start.byte_offset().unwrap_or_default();
+    let end = node.span.end.byte_offset().unwrap_or(text.len());
+    serde_saphyr::from_str(&text[start..end]).context("can't edit a flow collection with aliases")
+}
+
+fn is_null(node: &YamlNode, scalar: &str) -> bool {
+    node.span.start == node.span.end || matches!(scalar, "~" | "null" | "Null" | "NULL")
+}
+
+const fn is_empty_collection(node: &YamlNode) -> bool {
+    match &node.kind {
+        YamlNodeKind::Mapping(entries) => entries.is_empty(),
+        YamlNodeKind::Sequence(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn join<S: AsRef<str>>(path: &[S]) -> String {
+    path.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(".")
+}
+
+/// Wraps `value` in the mappings named by `path`.
+fn nest<S: AsRef<str>>(path: &[S], value: &Value) -> Value {
+    path.iter().rev().fold(value.clone(), |value, key| {
+        Value::Object(Map::from_iter([(key.as_ref().to_owned(), value)]))
+    })
+}
+
+fn set_in<S: AsRef<str>>(target: &mut Value, path: &[S], value: Value) -> anyhow::Result<()> {
+    let Some((first, rest)) = path.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+    let first = first.as_ref();
+    if target.is_null() {
+        *target = Value::Object(Map::new());
+    }
+    let child = match target {
+        Value::Object(map) => map.entry(first.to_owned()).or_insert(Value::Null),
+        Value::Array(items) => match first.parse::<usize>() {
+            Ok(index) if index == items.len() => {
+                items.push(Value::Null);
+                &mut items[index]
+            }
+            Ok(index) if index < items.len() => &mut items[index],
+            _ => bail!("index {first} is out of range"),
+        },
+        _ => bail!("a scalar can't have a '{first}' entry"),
+    };
+    set_in(child, rest, value)
+}
+
+fn remove_in<S: AsRef<str>>(target: &mut Value, path: &[S]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let parent = parents.iter().try_fold(target, |node, segment| {
+        let segment = segment.as_ref();
+        match node {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+            _ => None,
+        }
+    });
+    match parent {
+        Some(Value::Object(map)) => {
+            map.remove(last.as_ref());
+        }
+        Some(Value::Array(items)) => {
+            if let Ok(index) = last.as_ref().parse::<usize>()
+                && index < items.len()
+            {
+                items.remove(index);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Single-line YAML for scalars and empty collections.
+fn inline(value: &Value) -> Option<String> {
+    Some(match value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => scalar(value),
+        Value::Array(items) if items.is_empty() => "[]".to_owned(),
+        Value::Object(map) if map.is_empty() => "{}".to_owned(),
+        Value::Array(_) | Value::Object(_) => return None,
+    })
+}
+
+/// A string as a plain scalar when it reads back as the same string,
+/// double-quoted otherwise.
+fn scalar(value: &str) -> String {
+    let plain = value
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_alphanumeric() || matches!(first, '/' | '.' | '$' | '_'))
+        && value == value.trim_end()
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.ends_with(':')
+        && value.chars().all(|ch| {
+            !ch.is_control() && !matches!(ch, '{' | '}' | '[' | ']' | ',' | '#' | '"' | '\'')
+        })
+        && serde_saphyr::from_str::<Value>(value).ok() == Some(Value::String(value.to_owned()));
+    if plain {
+        value.to_owned()
+    } else {
+        Value::String(value.to_owned()).to_string()
+    }
+}
+
+/// Flow YAML for `value`, such as `[a, b]` or `{key: value}`.
+pub(super) fn flow(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(flow).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(key, value)| format!("{}: {}", scalar(key), flow(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        _ => inline(value).unwrap_or_default(),
+    }
+}
+
+/// Block YAML for `value`, every line indented by `indent` spaces.
+fn block(value: &Value, indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => map
+            .iter()
+            .map(|(key, value)| {
+                let key = scalar(key);
+                inline(value).map_or_else(
+                    || format!("{pad}{key}:\n{}", block(value, indent + 2)),
+                    |inline| format!("{pad}{key}: {inline}"),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Array(items) if !items.is_empty() => items
+            .iter()
+            .map(|item| {
+                let item = inline(item).unwrap_or_else(|| item_block(item, indent + 2));
+                format!("{pad}- {item}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => format!("{pad}{}", inline(value).unwrap_or_default()),
+    }
+}
+
+/// Block YAML for a sequence item whose first line follows the `- ` at
+/// `indent - 2`.
+fn item_block(value: &Value, indent: usize) -> String {
+    let rendered = block(value, indent);
+    rendered
+        .get(indent..)
+        .map_or_else(|| rendered.clone(), ToOwned::to_owned)
+}
+
+/// The changes that turn `old` into `new`. Mappings are compared entry by
+/// entry; any other difference replaces the value as a whole.
+#[must_use]
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_into(&mut Vec::new(), old, new, &mut changes);
+    changes
+}
+
+fn diff_into(path: &mut Vec<String>, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, value) in new {
+                path.push(key.clone());
+                match old.get(key) {
+                    Some(previous) => diff_into(path, previous, value, changes),
+                    None => changes.push(Change::Set(path.clone(), value.clone())),
+                }
+                path.pop();
+            }
+            // Removed last, so that a mapping doesn't collapse to `{}` before
+            // its new entries are in.
+            for key in old.keys().filter(|key| !new.contains_key(*key)) {
+                path.push(key.clone());
+                changes.push(Change::Unset(path.clone()));
+                path.pop();
+            }
+        }
+        _ if old == new => {}
+        _ => changes.push(Change::Set(path.clone(), new.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, ConfigDocument, diff};
+    use serde_json::json;
+
+    const CONFIG: &str = "# Local development server
+server:
+  home_dir: ~/.cyberfabric # data lives here
+
+modules:
+  # Public API
+  api-gateway:
+    config:
+      bind_addr: \"0.0.0.0:8080\"
+      cors: [\"*\"]
+  users:
+    metadata: {}
+    database:
+      server: main
+
+# Keep this last
+vendor:
+  acme:
+    enabled: true
+";
+
+    fn edited(edit: impl FnOnce(&mut ConfigDocument)) -> String {
+        let mut document = ConfigDocument::new(CONFIG.to_owned());
+        edit(&mut document);
+        document.into_text()
+    }
+
+    #[test]
+    fn replaces_values_in_place() {
+        let text = edited(|doc| {
+            doc.set(&["server", "home_dir"], &json!("/var/lib/cf"))
+                .expect("set");
+            doc.set(
+                &["modules", "users", "database"],
+                &json!({ "pool": 4, "server": "replica" }),
+            )
+            .expect("set");
+        });
+
+        assert_eq!(
+            text,
+            CONFIG
+                .replace("home_dir: ~/.cyberfabric #", "home_dir: /var/lib/cf #")
+                .replace(
+                    "    database:\n      server: main\n",
+                    "    database:\n      pool: 4\n      server: replica\n"
+                )
+        );
+    }
+
+    #[test]
+    fn inserts_entries_next_to_their_siblings() {
+        let text = edited(|doc| {
+            doc.set(
+                &["modules", "api-gateway", "config", "timeout_ms"],
+                &json!(500),
+            )
+            .expect("set");
+            doc.set(
+                &["modules", "users", "metadata", "package"],
+                &json!("cf-users"),
+            )
+            .expect("set");
+            doc.set(&["modules", "orders", "config", "batch_size"], &json!(10))
+                .expect("set");
+            doc.set(&["vendor", "acme", "tags"], &json!(["a", "b c"]))
+                .expect("set");
+        });
+
+        assert_eq!(
+            text,
+            CONFIG
+                .replace(
+                    "      cors: [\"*\"]\n",
+                    "      cors: [\"*\"]\n      timeout_ms: 500\n"
+                )
+                .replace(
+                    "    metadata: {}\n",
+                    "    metadata:\n      package: cf-users\n"
+                )
+                .replace(
+                    "      server: main\n",
+                    "      server: main\n  orders:\n    config:\n      batch_size: 10\n"
+                )
+                .replace(
+                    "    enabled: true\n",
+                    "    enabled: true\n    tags:\n      - a\n      - b c\n"
+                )
+        );
+    }
+
+    #[test]
+    fn edits_inside_flow_collections_rewrite_the_collection() {
+        let text = edited(|doc| {
+            doc.set(
+                &["modules", "api-gateway", "config", "cors", "1"],
+                &json!("https://app"),
+            )
+            .expect("set");
+        });
+
+        assert!(text.contains("      cors: [\"*\", https://app]\n"));
+
+        let text = edited(|doc| {
+            doc.set(
+                &["modules", "api-gateway", "config", "cors"],
+                &json!(["https://a", "https://b"]),
+            )
+            .expect("set");
+        });
+
+        assert!(text.contains("      cors: [https://a, https://b]\n"));
+    }
+
+    #[test]
+    fn edits_inside_flow_collections_keep_untouched_entries() {
+        let config = "gateway: {bind_addr: \"0.0.0.0:8080\", tls: {cert: 'a.pem'}}\ncors: ['*', \"https://a\", 'https://b']\n";
+        let mut doc = ConfigDocument::new(config.to_owned());
+        doc.set(&["gateway", "port"], &json!(80)).expect("set");
+        doc.set(&["gateway", "tls", "key"], &json!("a.key"))
+            .expect("set");
+        assert!(doc.unset(&["cors", "1"]).expect("unset"));
+
+        assert_eq!(
+            doc.into_text(),
+            "gateway: {bind_addr: \"0.0.0.0:8080\", tls: {cert: 'a.pem', key: a.key}, port: 80}\ncors: ['*', 'https://b']\n"
+        );
+    }
+
+    #[test]
+    fn removes_entries_with_their_lines() {
+        let text = edited(|doc| {
+            assert!(
+                doc.unset(&["modules", "api-gateway", "config", "cors"])
+                    .expect("unset")
+            );
+            assert!(
+                doc.unset(&["modules", "users", "database", "server"])
+                    .expect("unset")
+            );
+            assert!(!doc.unset(&["modules", "missing"]).expect("unset"));
+        });
+
+        assert_eq!(
+            text,
+            CONFIG
+                .replace("      cors: [\"*\"]\n", "")
+                .replace("    database:\n      server: main\n", "    database: {}\n")
+        );
+    }
+
+    #[test]
+    fn applies_the_diff_of_two_configs() {
+        let old = json!({ "server": { "home_dir": "~/.cyberfabric" }, "modules": {
+            "api-gateway": { "config": { "bind_addr": "0.0.0.0:8080", "cors": ["*"] } },
+            "users": { "metadata": {}, "database": { "server": "main" } }
+        }});
+        let mut new = old.clone();
+        new["modules"]["users"]["metadata"] = json!({ "version": "1.2" });
+        new["modules"]
+            .as_object_mut()
+            .expect("modules")
+            .remove("api-gateway");
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            [
+                Change::Set(
+                    vec![
+                        "modules".into(),
+                        "users".into(),
+                        "metadata".into(),
+                        "version".into()
+                    ],
+                    json!("1.2")
+                ),
+                Change::Unset(vec!["modules".into(), "api-gateway".into()]),
+            ]
+        );
+
+        let text = edited(|doc| doc.apply(&changes).expect("apply"));
+        assert_eq!(
+            text,
+            "# Local development server
+server:
+  home_dir: ~/.cyberfabric # data lives here
+
+modules:
+  users:
+    metadata:
+      version: \"1.2\"
+    database:
+      server: main
+
+# Keep this last
+vendor:
+  acme:
+    enabled: true
+"
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! `${VAR}` and `${VAR:-default}` expansion of config values.
+//!
+//! Only string values are expanded; mapping keys are left alone. `$${` is an
+//! escape for a literal `${`. Variables are looked up in the process
+//! environment first, then in the `--env-file`.
+
+use anyhow::{Context, bail};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Variables available to the expansion.
+#[derive(Debug, Default)]
+pub struct EnvVars {
+    file: BTreeMap<String, String>,
+}
+
+impl EnvVars {
+    pub fn load(env_file: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = env_file else {
+            return Ok(Self::default());
+        };
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("can't read env file {}", path.display()))?;
+        let file =
+            parse_env_file(&raw).with_context(|| format!("invalid env file {}", path.display()))?;
+        Ok(Self { file })
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        env::var(name).ok().or_else(|| self.file.get(name).cloned())
+    }
+}
+
+/// Parses `KEY=VALUE` lines. Blank lines, `#` comments and an `export ` prefix
+/// are allowed, and a value wrapped in matching quotes is unquoted.
+fn parse_env_file(raw: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+    for (index, line) in raw.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("line {}: expected KEY=VALUE", index + 1);
+        };
+        let key = key.trim();
+        if !is_var_name(key) {
+            bail!("line {}: invalid variable name '{key}'", index + 1);
+        }
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|quote| {
+                value
+                    .strip_prefix(*quote)
+                    .and_then(|rest| rest.strip_suffix(*quote))
+            })
+            .unwrap_or(value);
+        vars.insert(key.to_owned(), value.to_owned());
+    }
+    Ok(vars)
+}
+
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Expands every string of `value` in place. All unset variables and malformed
+/// placeholders are reported together, with the config path they appear at.
+pub fn interpolate(value: &mut Value, vars: &EnvVars) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+    interpolate_at(value, "", &|name| vars.get(name), &mut problems);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "config references unresolved environment variables:\n  {}\nset them or pass them with --env-file",
+        problems.join("\n  ")
+    );
+}
+
+fn interpolate_at(
+    value: &mut Value,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    problems: &mut Vec<String>,
+) {
+    match value {
+        Value::String(raw) => match expand(raw, lookup) {
+            Ok(expanded) => *raw = expanded,
+            Err(message) => problems.push(format!("{message} (at {path})")),
+        },
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                interpolate_at(item, &format!("{path}[{index}]"), lookup, problems);
+            }
+        }
+        Value::Object(entries) => {
+            for (key, item) in entries.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                interpolate_at(item, &path, lookup, problems);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// The variables referenced by the placeholders of `value`, each with whether
+/// every reference to it has a default.
+#[must_use]
+pub fn placeholders(value: &Value) -> BTreeMap<String, bool> {
+    let mut found = BTreeMap::new();
+    collect_placeholders(value, &mut found);
+    found
+}
+
+fn collect_placeholders(value: &Value, found: &mut BTreeMap<String, bool>) {
+    match value {
+        Value::String(raw) => {
+            // Same grammar as `expand`.
+            let mut rest = raw.as_str();
+            while let Some(start) = rest.find('$') {
+                let tail = &rest[start..];
+                if let Some(escaped) = tail.strip_prefix("$${") {
+                    rest = escaped;
+                    continue;
+                }
+                let Some(body) = tail.strip_prefix("${") else {
+                    rest = &tail[1..];
+                    continue;
+                };
+                let Some(end) = body.find('}') else { break };
+                let (name, has_default) = body[..end]
+                    .split_once(":-")
+                    .map_or((&body[..end], false), |(name, _)| (name, true));
+                if is_var_name(name) {
+                    let all_defaulted = found.entry(name.to_owned()).or_insert(true);
+                    *all_defaulted &= has_default;
+                }
+                rest = &body[end + 1..];
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_placeholders(item, found)),
+        Value::Object(entries) => entries
+            .values()
+            .for_each(|item| collect_placeholders(item, found)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Whether `raw` is a single placeholder such as `${PORT}` or
+/// `${PORT:-8080}`, which resolves to a value of any type once expanded.
+#[must_use]
+pub fn is_placeholder(raw: &str) -> bool {
+    raw.strip_prefix("${")
+        .and_then(|body| body.strip_suffix('}'))
+        .filter(|body| !body.contains('}'))
+        .is_some_and(|body| is_var_name(body.split_once(":-").map_or(body, |(name, _)| name)))
+}
+
+/// Expands the placeholders of a single string.
+fn expand(raw: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut missing = Vec::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if let Some(escaped) = tail.strip_prefix("$${") {
+            out.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(body) = tail.strip_prefix("${") else {
+            out.push('$');
+            rest = &tail[1..];
+            continue;
+        };
+        let end = body
+            .find('}')
+            .ok_or_else(|| format!("unterminated placeholder '{tail}'"))?;
+        let (name, default) = match body[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&body[..end], None),
+        };
+        if !is_var_name(name) {
+            return Err(format!("invalid variable name in '${{{}}}'", &body[..end]));
+        }
+        match (lookup(name).filter(|value| !value.is_empty()), default) {
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) if lookup(name).is_some() => {}
+            (None, None) => missing.push(name.to_owned()),
+        }
+        rest = &body[end + 1..];
+    }
+    out.push_str(rest);
+    if missing.is_empty() {
+        Ok(out)
+    } else {
+        Err(format!("{} is not set", missing.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, interpolate_at, parse_env_file, placeholders};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn lookup(name: &str) -> Option<String> {
+        BTreeMap::from([
+            ("HOST", "db.acme.io"),
+            ("PASSWORD", "s3cr:t#1"),
+            ("EMPTY", ""),
+        ])
+        .get(name)
+        .map(|value| (*value).to_owned())
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn expands_placeholders_and_defaults() {
+        assert_eq!(
+            expand(
+                "postgres://app:${PASSWORD}@${HOST}:${PORT:-5432}/app",
+                &lookup
+            ),
+            Ok("postgres://app:s3cr:t#1@db.acme.io:5432/app".to_owned())
+        );
+        assert_eq!(
+            expand("${EMPTY:-fallback}|${EMPTY}", &lookup),
+            Ok("fallback|".to_owned())
+        );
+        assert_eq!(
+            expand("cost: $5, literal $${HOST}", &lookup),
+            Ok("cost: $5, literal ${HOST}".to_owned())
+        );
+        assert_eq!(
+            expand("${MISSING}/${OTHER}", &lookup),
+            Err("MISSING, OTHER is not set".to_owned())
+        );
+        assert!(expand("${HOST", &lookup).is_err());
+        assert!(expand("${1BAD}", &lookup).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn lists_placeholders_and_whether_they_have_defaults() {
+        let config = json!({
+            "dsn": "postgres://app:${PASSWORD}@${HOST:-db}:5432",
+            "hosts": ["${HOST}", "$${NOT_A_VAR}", "${PORT:-5432}"],
+        });
+
+        assert_eq!(
+            placeholders(&config),
+            BTreeMap::from([
+                ("HOST".to_owned(), false),
+                ("PASSWORD".to_owned(), false),
+                ("PORT".to_owned(), true),
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_every_unresolved_value_with_its_path() {
+        let mut config = json!({
+            "database": { "servers": { "main": { "dsn": "${DSN}", "port": 5432 } } },
+            "modules": { "worker": { "runtime": { "execution": {
+                "environment": { "TOKEN": "${TOKEN}", "HOST": "${HOST}" }
+            } } } },
+            "exporters": [{ "headers": { "x-key": "${HOST}" } }]
+        });
+        let mut problems = Vec::new();
+
+        interpolate_at(&mut config, "", &lookup, &mut problems);
+
+        assert_eq!(
+            problems,
+            vec![
+                "DSN is not set (at database.servers.main.dsn)",
+                "TOKEN is not set (at modules.worker.runtime.execution.environment.TOKEN)",
+            ]
+        );
+        assert_eq!(config["exporters"][0]["headers"]["x-key"], "db.acme.io");
+    }
+
+    #[test]
+    fn parses_env_files() {
+        let vars = parse_env_file(
+            "# secrets\nexport DB_PASSWORD='p@ss word'\n\nHOST = db.local\nEMPTY=\n",
+        )
+        .expect("env file parses");
+
+        assert_eq!(vars["DB_PASSWORD"], "p@ss word");
+        assert_eq!(vars["HOST"], "db.local");
+        assert_eq!(vars["EMPTY"], "");
+        assert!(parse_env_file("NOT A PAIR\n").is_err());
+    }
+}
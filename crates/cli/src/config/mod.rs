@@ -7,7 +7,16 @@ use anyhow::{Context, bail};
 use crate::app_config::{AppConfig, DbConnConfig};
 
 mod db;
+mod diff;
+mod document;
+pub mod env;
 mod modules;
+pub mod overlay;
+mod render;
+pub mod schema;
+mod validate;
+mod value;
+mod yaml;
 
 #[derive(Args)]
 pub struct ConfigArgs {
@@ -25,6 +34,20 @@ impl ConfigArgs {
 pub enum ConfigCommand {
     Mod(modules::ModulesArgs),
     Db(Box<db::DbArgs>),
+    /// Check the config for problems and report them with their YAML positions
+    Validate(validate::ValidateArgs),
+    /// Print the config with its layers merged and `${VAR}` placeholders expanded
+    Render(render::RenderArgs),
+    /// Print the JSON Schema of the config format, including module config schemas
+    Schema(schema::SchemaArgs),
+    /// Print the value at a dotted path, such as `modules.users.config`
+    Get(value::GetArgs),
+    /// Set the value at a dotted path, keeping the rest of the file as it is
+    Set(value::SetArgs),
+    /// Remove the entry at a dotted path, keeping the rest of the file as it is
+    Unset(value::UnsetArgs),
+    /// Compare two configs, or with `--deps` the server dependencies they generate
+    Diff(diff::DiffArgs),
 }
 
 impl ConfigCommand {
@@ -32,23 +55,58 @@ impl ConfigCommand {
         match self {
             Self::Mod(args) => args.run(),
             Self::Db(args) => args.run(),
+            Self::Validate(args) => args.run(),
+            Self::Render(args) => args.run(),
+            Self::Schema(args) => args.run(),
+            Self::Get(args) => args.run(),
+            Self::Set(args) => args.run(),
+            Self::Unset(args) => args.run(),
+            Self::Diff(args) => args.run(),
         }
     }
 }
 
+fn read_config(path: &Path) -> anyhow::Result<String> {
+    fs::read_to_string(path).with_context(|| format!("can't read config file {}", path.display()))
+}
+
 pub fn load_config(path: &Path) -> anyhow::Result<AppConfig> {
-    let raw = fs::read_to_string(path)
-        .with_context(|| format!("can't read config file {}", path.display()))?;
-    serde_saphyr::from_str(&raw).with_context(|| format!("config not valid at {}", path.display()))
+    serde_saphyr::from_str(&read_config(path)?)
+        .with_context(|| format!("config not valid at {}", path.display()))
 }
 
-pub fn save_config(path: &Path, config: &AppConfig) -> anyhow::Result<()> {
-    let mut serialized = serde_saphyr::to_string(config).context("failed to serialize config")?;
-    if !serialized.ends_with('\n') {
-        serialized.push('\n');
-    }
+/// Applies `edit` to the config at `path` and returns the file's new text, in
+/// which only the entries `edit` changed are rewritten: comments, key order
+/// and formatting elsewhere are kept byte for byte.
+pub fn edit_config<T>(
+    path: &Path,
+    edit: impl FnOnce(&mut AppConfig) -> anyhow::Result<T>,
+) -> anyhow::Result<(String, T)> {
+    let raw = read_config(path)?;
+    let mut config: AppConfig = serde_saphyr::from_str(&raw)
+        .with_context(|| format!("config not valid at {}", path.display()))?;
+    let before = serde_json::to_value(&config).context("failed to serialize config")?;
+    let edited = edit(&mut config)?;
+    let after = serde_json::to_value(&config).context("failed to serialize config")?;
+
+    let mut document = document::ConfigDocument::new(raw);
+    document.apply(&document::diff(&before, &after))?;
+    Ok((document.into_text(), edited))
+}
+
+/// [`edit_config`], writing the result back to `path`.
+pub fn update_config<T>(
+    path: &Path,
+    edit: impl FnOnce(&mut AppConfig) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let (text, edited) = edit_config(path, edit)?;
+    write_config(path, &text)?;
+    Ok(edited)
+}
+
+pub fn write_config(path: &Path, contents: &str) -> anyhow::Result<()> {
     let tmp_path = path.with_extension("tmp");
-    fs::write(&tmp_path, serialized)
+    fs::write(&tmp_path, contents)
         .with_context(|| format!("can't write temp config file {}", tmp_path.display()))?;
     fs::rename(&tmp_path, path)
         .with_context(|| format!("can't replace config file {}", path.display()))
@@ -71,3 +129,47 @@ pub fn ensure_conn_payload(conn: &DbConnConfig) -> anyhow::Result<()> {
     }
     bail!("no database fields provided")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::update_config;
+    use std::fs;
+
+    #[test]
+    fn update_config_only_rewrites_what_changed() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let path = temp_dir.path().join("quickstart.yml");
+        let original = "# Local development server
+server:
+  home_dir: ~/.cyberfabric # data lives here
+
+modules:
+  # Public API
+  api-gateway:
+    config: {bind_addr: \"0.0.0.0:8080\"}
+  # Drop me
+  users:
+    metadata: {}
+";
+        fs::write(&path, original).expect("write config");
+
+        update_config(&path, |config| {
+            config.modules.remove("users");
+            Ok(())
+        })
+        .expect("update");
+
+        assert_eq!(
+            fs::read_to_string(&path).expect("read config"),
+            "# Local development server
+server:
+  home_dir: ~/.cyberfabric # data lives here
+
+modules:
+  # Public API
+  api-gateway:
+    config: {bind_addr: \"0.0.0.0:8080\"}
+"
+        );
+    }
+}
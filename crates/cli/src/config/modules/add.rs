@@ -1,4 +1,4 @@
-use super::{load_config, resolve_modules_context, save_config, validate_module_name};
+use super::{resolve_modules_context, update_config, validate_module_name};
 use crate::app_config::AppConfig;
 use crate::common::PathConfigArgs;
 use anyhow::{Context, bail};
@@ -34,13 +34,13 @@ impl AddArgs {
         validate_module_name(&self.module)?;
         let context = resolve_modules_context(&self.path_config)?;
 
-        let mut config = load_config(&context.config_path)?;
         let local_modules = discover_local_modules(self)?;
         let metadata = build_required_metadata(self, local_modules.get(&self.module))?;
 
-        upsert_module_config(&mut config, self, metadata);
-
-        save_config(&context.config_path, &config)
+        update_config(&context.config_path, |config| {
+            upsert_module_config(config, self, metadata);
+            Ok(())
+        })
     }
 }
 
@@ -188,7 +188,7 @@ mod tests {
         let args = AddArgs {
             path_config: PathConfigArgs {
                 path: Some(PathBuf::from(".")),
-                config: PathBuf::from("."),
+                config: Some(PathBuf::from(".")),
             },
             module: "demo".to_owned(),
             package: None,
@@ -204,6 +204,7 @@ mod tests {
                 deps: vec!["tenant-resolver".to_owned()],
                 ..ConfigModuleMetadata::default()
             },
+            config_schema: None,
         };
 
         let metadata = build_required_metadata(&args, Some(&local_module)).expect("metadata");
@@ -220,7 +221,7 @@ mod tests {
         let args = AddArgs {
             path_config: PathConfigArgs {
                 path: Some(PathBuf::from(".")),
-                config: PathBuf::from("."),
+                config: Some(PathBuf::from(".")),
             },
             module: "demo".to_owned(),
             package: None,
@@ -244,7 +245,7 @@ mod tests {
         let args = AddArgs {
             path_config: PathConfigArgs {
                 path: Some(PathBuf::from(".")),
-                config: PathBuf::from("."),
+                config: Some(PathBuf::from(".")),
             },
             module: "demo".to_owned(),
             package: Some("cf-demo".to_owned()),
@@ -268,7 +269,7 @@ mod tests {
         let args = AddArgs {
             path_config: PathConfigArgs {
                 path: Some(PathBuf::from(".")),
-                config: PathBuf::from("."),
+                config: Some(PathBuf::from(".")),
             },
             module: "demo".to_owned(),
             package: Some("cf-demo".to_owned()),
@@ -305,7 +306,7 @@ mod tests {
         let args = AddArgs {
             path_config: PathConfigArgs {
                 path: Some(PathBuf::from(".")),
-                config: PathBuf::from("."),
+                config: Some(PathBuf::from(".")),
             },
             module: "demo".to_owned(),
             package: None,
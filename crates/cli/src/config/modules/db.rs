@@ -1,4 +1,4 @@
-use super::{load_config, resolve_modules_context, save_config, validate_module_name};
+use super::{resolve_modules_context, update_config, validate_module_name};
 use crate::app_config::{AppConfig, DbConnConfig, ModuleConfig};
 use crate::common::PathConfigArgs;
 use crate::config::ensure_conn_payload;
@@ -47,21 +47,22 @@ impl AddArgs {
         validate_module_db_payload(&self.module, &self.conn)?;
 
         let context = resolve_modules_context(&self.path_config)?;
-        let mut config = load_config(&context.config_path)?;
-        if !config.modules.contains_key(&self.module) {
-            bail!(
-                "module '{}' not found in {}; use `config mod add` first",
-                self.module,
-                context.config_path.display()
-            );
-        }
-        let module_cfg = get_module_cfg_mut(&mut config, &self.module, &context.config_path)?;
-        if let Some(existing) = module_cfg.database.as_mut() {
-            existing.apply_patch(self.conn.clone());
-        } else {
-            module_cfg.database = Some(self.conn.clone());
-        }
-        save_config(&context.config_path, &config)
+        update_config(&context.config_path, |config| {
+            if !config.modules.contains_key(&self.module) {
+                bail!(
+                    "module '{}' not found in {}; use `config mod add` first",
+                    self.module,
+                    context.config_path.display()
+                );
+            }
+            let module_cfg = get_module_cfg_mut(config, &self.module, &context.config_path)?;
+            if let Some(existing) = module_cfg.database.as_mut() {
+                existing.apply_patch(self.conn.clone());
+            } else {
+                module_cfg.database = Some(self.conn.clone());
+            }
+            Ok(())
+        })
     }
 }
 
@@ -80,17 +81,17 @@ impl EditArgs {
         validate_module_db_payload(&self.module, &self.conn)?;
 
         let context = resolve_modules_context(&self.path_config)?;
-        let mut config = load_config(&context.config_path)?;
-        let module_cfg = get_module_cfg_mut(&mut config, &self.module, &context.config_path)?;
-        let db_cfg = module_cfg.database.as_mut().with_context(|| {
-            format!(
-                "module '{}' has no database config; use `config mod db add` first",
-                self.module
-            )
-        })?;
-        db_cfg.apply_patch(self.conn.clone());
-
-        save_config(&context.config_path, &config)
+        update_config(&context.config_path, |config| {
+            let module_cfg = get_module_cfg_mut(config, &self.module, &context.config_path)?;
+            let db_cfg = module_cfg.database.as_mut().with_context(|| {
+                format!(
+                    "module '{}' has no database config; use `config mod db add` first",
+                    self.module
+                )
+            })?;
+            db_cfg.apply_patch(self.conn.clone());
+            Ok(())
+        })
     }
 }
 
@@ -107,14 +108,14 @@ impl RemoveArgs {
         validate_module_name(&self.module)?;
 
         let context = resolve_modules_context(&self.path_config)?;
-        let mut config = load_config(&context.config_path)?;
-        let module_cfg = get_module_cfg_mut(&mut config, &self.module, &context.config_path)?;
-        if module_cfg.database.take().is_none() {
-            let module = &self.module;
-            bail!("module '{module}' has no database config");
-        }
-
-        save_config(&context.config_path, &config)
+        update_config(&context.config_path, |config| {
+            let module_cfg = get_module_cfg_mut(config, &self.module, &context.config_path)?;
+            if module_cfg.database.take().is_none() {
+                let module = &self.module;
+                bail!("module '{module}' has no database config");
+            }
+            Ok(())
+        })
     }
 }
 
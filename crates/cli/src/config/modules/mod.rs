@@ -1,4 +1,4 @@
-use super::{load_config, save_config, validate_name};
+use super::{load_config, update_config, validate_name};
 use crate::common::PathConfigArgs;
 use clap::{Args, Subcommand};
 use std::path::PathBuf;
@@ -1,4 +1,4 @@
-use super::{load_config, resolve_modules_context, save_config, validate_module_name};
+use super::{resolve_modules_context, update_config, validate_module_name};
 use crate::common::PathConfigArgs;
 use anyhow::bail;
 use clap::Args;
@@ -16,12 +16,12 @@ impl RemoveArgs {
         validate_module_name(&self.module)?;
         let context = resolve_modules_context(&self.path_config)?;
 
-        let mut config = load_config(&context.config_path)?;
-        if config.modules.remove(&self.module).is_none() {
-            let module = &self.module;
-            bail!("module '{module}' not found in modules section");
-        }
-
-        save_config(&context.config_path, &config)
+        update_config(&context.config_path, |config| {
+            if config.modules.remove(&self.module).is_none() {
+                let module = &self.module;
+                bail!("module '{module}' not found in modules section");
+            }
+            Ok(())
+        })
     }
 }
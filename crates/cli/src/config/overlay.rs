@@ -0,0 +1,142 @@
+//! Layered configs: a base file plus overlays, deep-merged in order.
+//!
+//! Mappings merge key by key, so `modules`, `database.servers` and `vendor`
+//! entries of an overlay are merged into the base entries of the same name.
+//! Any other value, sequences included, replaces the base value, and a `null`
+//! removes the key from the result.
+
+use anyhow::{Context, bail};
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Reads every layer and merges them, later layers winning.
+pub fn merge_layers(layers: &[PathBuf]) -> anyhow::Result<Value> {
+    let mut merged = Value::Object(serde_json::Map::new());
+    for layer in layers {
+        let raw = fs::read_to_string(layer)
+            .with_context(|| format!("can't read config file {}", layer.display()))?;
+        let value: Value = serde_saphyr::from_str(&raw)
+            .with_context(|| format!("config not valid at {}", layer.display()))?;
+        match value {
+            Value::Null => {}
+            Value::Object(_) => merge(&mut merged, value),
+            _ => bail!("config {} must be a YAML mapping", layer.display()),
+        }
+    }
+    Ok(merged)
+}
+
+/// Merges `overlay` into `base` following the module level rules.
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                } else if let Some(existing) = base.get_mut(&key) {
+                    merge(existing, value);
+                } else {
+                    base.insert(key, value);
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Serializes a merged config as YAML.
+pub fn to_yaml(value: &Value) -> anyhow::Result<String> {
+    let mut serialized = serde_saphyr::to_string(value).context("failed to serialize config")?;
+    if !serialized.ends_with('\n') {
+        serialized.push('\n');
+    }
+    Ok(serialized)
+}
+
+/// Writes the merged config as YAML, replacing any previous file. The file is
+/// only readable by its owner since expanded configs can hold secrets.
+pub fn write_merged(value: &Value, path: &Path) -> anyhow::Result<()> {
+    let serialized = to_yaml(value)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("can't create directory {}", parent.display()))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(serialized.as_bytes()))
+        .with_context(|| format!("can't write merged config {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, merge_layers};
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn overlays_merge_sections_by_key() {
+        let mut base = json!({
+            "server": { "home_dir": "/srv", "port": 8080 },
+            "database": { "servers": {
+                "main": { "host": "localhost", "port": 5432 },
+                "cache": { "engine": "sqlite" }
+            }},
+            "modules": {
+                "api-gateway": { "config": { "bind": "0.0.0.0:8080", "cors": ["*"] } },
+                "debug-tools": { "config": {} }
+            },
+            "vendor": { "acme": { "region": "eu", "tier": "free" } }
+        });
+        let overlay = json!({
+            "server": { "port": 9090 },
+            "database": { "servers": { "main": { "host": "db.prod" }, "cache": null } },
+            "modules": {
+                "api-gateway": { "config": { "cors": ["https://acme.io"] } },
+                "debug-tools": null,
+                "billing": { "config": { "currency": "EUR" } }
+            },
+            "vendor": { "acme": { "tier": "gold" } }
+        });
+
+        merge(&mut base, overlay);
+
+        assert_eq!(
+            base,
+            json!({
+                "server": { "home_dir": "/srv", "port": 9090 },
+                "database": { "servers": { "main": { "host": "db.prod", "port": 5432 } } },
+                "modules": {
+                    "api-gateway": { "config": { "bind": "0.0.0.0:8080", "cors": ["https://acme.io"] } },
+                    "billing": { "config": { "currency": "EUR" } }
+                },
+                "vendor": { "acme": { "region": "eu", "tier": "gold" } }
+            })
+        );
+    }
+
+    #[test]
+    fn merges_layer_files_in_order() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let base = temp_dir.path().join("base.yml");
+        let empty = temp_dir.path().join("empty.yml");
+        let prod = temp_dir.path().join("prod.yml");
+        fs::write(&base, "server:\n  home_dir: /srv\nmodules:\n  a: {}\n").expect("write");
+        fs::write(&empty, "").expect("write");
+        fs::write(&prod, "server:\n  home_dir: /opt\n").expect("write");
+
+        let merged = merge_layers(&[base, empty, prod.clone()]).expect("layers merge");
+        assert_eq!(
+            merged,
+            json!({ "server": { "home_dir": "/opt" }, "modules": { "a": {} } })
+        );
+
+        fs::write(&prod, "- not a mapping\n").expect("write");
+        assert!(merge_layers(&[prod]).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use super::env::{EnvVars, interpolate};
+use super::overlay;
+use crate::common::LayeredConfigArgs;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct RenderArgs {
+    #[command(flatten)]
+    layers: LayeredConfigArgs,
+    /// Dotenv file with variables for the expansion
+    #[arg(long, value_name = "PATH")]
+    env_file: Option<PathBuf>,
+    /// Write the rendered config to this file instead of stdout
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+impl RenderArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let layers = self.layers.resolve_layers()?;
+        let mut config = overlay::merge_layers(layers.paths())?;
+        interpolate(&mut config, &EnvVars::load(self.env_file.as_deref())?)?;
+
+        if let Some(output) = &self.output {
+            return overlay::write_merged(&config, output);
+        }
+        print!("{}", overlay::to_yaml(&config)?);
+        Ok(())
+    }
+}
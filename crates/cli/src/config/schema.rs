@@ -0,0 +1,351 @@
+use super::env;
+use crate::app_config::AppConfig;
+use crate::common::parse_and_chdir;
+use anyhow::{Context, bail};
+use clap::Args;
+use module_parser::{ConfigModule, get_module_name_from_crate};
+use schemars::generate::SchemaSettings;
+use serde_json::{Map, Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct SchemaArgs {
+    /// Path to the module workspace root
+    #[arg(short = 'p', long, value_parser = parse_and_chdir)]
+    path: Option<PathBuf>,
+    /// Write the schema to this file instead of stdout
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+impl SchemaArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let mut schema = config_schema()?;
+        match get_module_name_from_crate() {
+            Ok(modules) => {
+                for (name, module_schema) in load_module_schemas(&modules)? {
+                    add_module_schema(&mut schema, &name, module_schema)?;
+                }
+            }
+            Err(err) => eprintln!(
+                "warning: can't discover workspace modules, their config schemas are left out: {err:#}"
+            ),
+        }
+
+        let rendered =
+            serde_json::to_string_pretty(&schema).context("failed to serialize schema")?;
+        if let Some(output) = &self.output {
+            return fs::write(output, rendered + "\n")
+                .with_context(|| format!("can't write schema to {}", output.display()));
+        }
+        println!("{rendered}");
+        Ok(())
+    }
+}
+
+/// JSON Schema (draft-07, the most widely supported by editors) of the config format.
+pub fn config_schema() -> anyhow::Result<Value> {
+    let schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<AppConfig>();
+    serde_json::to_value(schema).context("failed to serialize schema")
+}
+
+/// Reads the config schemas shipped by workspace modules, keyed by module name.
+pub fn load_module_schemas(
+    modules: &HashMap<String, ConfigModule>,
+) -> anyhow::Result<BTreeMap<String, Value>> {
+    modules
+        .iter()
+        .filter_map(|(name, module)| module.config_schema.as_ref().map(|path| (name, path)))
+        .map(|(name, path)| {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("can't read {}", path.display()))?;
+            let schema = serde_json::from_str(&raw)
+                .with_context(|| format!("invalid JSON schema {}", path.display()))?;
+            Ok((name.clone(), schema))
+        })
+        .collect()
+}
+
+/// A `modules.<module>.config` value rejected by the module's schema.
+#[derive(Debug)]
+pub struct ModuleConfigIssue {
+    /// Config path of the offending value, from the document root.
+    pub path: Vec<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ModuleConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.join("."), self.message)
+    }
+}
+
+/// Checks the `config` bag of every module that has a schema. Values that
+/// are a single `${VAR}` placeholder are only known at runtime, so they are
+/// accepted whatever type the schema expects.
+pub fn module_config_issues(
+    config: &AppConfig,
+    schemas: &BTreeMap<String, Value>,
+) -> anyhow::Result<Vec<ModuleConfigIssue>> {
+    let mut issues = Vec::new();
+    for (name, module) in &config.modules {
+        let Some(schema) = schemas.get(name) else {
+            continue;
+        };
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|err| anyhow::anyhow!("invalid config schema of module '{name}': {err}"))?;
+        for error in validator.iter_errors(&module.config) {
+            if error.instance().as_str().is_some_and(env::is_placeholder) {
+                continue;
+            }
+            let mut path = vec!["modules".to_owned(), name.clone(), "config".to_owned()];
+            path.extend(
+                error
+                    .instance_path()
+                    .to_string()
+                    .split('/')
+                    .skip(1)
+                    .map(|segment| segment.replace("~1", "/").replace("~0", "~")),
+            );
+            issues.push(ModuleConfigIssue {
+                path,
+                message: error.to_string(),
+            });
+        }
+    }
+    Ok(issues)
+}
+
+/// Types `modules.<module>.config` with the schema the module ships. The
+/// module's own definitions move to the root, prefixed with the module name.
+pub fn add_module_schema(
+    schema: &mut Value,
+    module: &str,
+    mut module_schema: Value,
+) -> anyhow::Result<()> {
+    let Some(module_root) = module_schema.as_object_mut() else {
+        bail!("config schema of module '{module}' must be a JSON object");
+    };
+    module_root.remove("$schema");
+    let mut definitions = Map::new();
+    for key in ["$defs", "definitions"] {
+        if let Some(Value::Object(defs)) = module_root.remove(key) {
+            definitions.extend(defs);
+        }
+    }
+    let prefix = format!("{module}.");
+    rewrite_refs(&mut module_schema, &prefix);
+
+    let root = schema
+        .as_object_mut()
+        .context("config schema must be a JSON object")?;
+    let root_definitions = root
+        .entry("definitions")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .context("config schema definitions must be an object")?;
+    for (name, mut definition) in definitions {
+        rewrite_refs(&mut definition, &prefix);
+        root_definitions.insert(format!("{prefix}{name}"), definition);
+    }
+
+    let modules = root
+        .get_mut("properties")
+        .and_then(|properties| properties.get_mut("modules"))
+        .and_then(Value::as_object_mut)
+        .context("config schema has no `modules` property")?;
+    let properties = modules
+        .entry("properties")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .context("`modules.properties` must be an object")?;
+    properties.insert(
+        module.to_owned(),
+        json!({
+            "allOf": [
+                { "$ref": "#/definitions/ModuleConfig" },
+                { "properties": { "config": module_schema } }
+            ]
+        }),
+    );
+    Ok(())
+}
+
+/// Points the local `#/$defs/..` and `#/definitions/..` references of a module
+/// schema to the hoisted, prefixed definitions.
+fn rewrite_refs(value: &mut Value, prefix: &str) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if key == "$ref"
+                    && let Value::String(reference) = item
+                    && let Some(name) = reference
+                        .strip_prefix("#/$defs/")
+                        .or_else(|| reference.strip_prefix("#/definitions/"))
+                {
+                    *reference = format!("#/definitions/{prefix}{name}");
+                } else {
+                    rewrite_refs(item, prefix);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rewrite_refs(item, prefix);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_module_schema, config_schema, module_config_issues};
+    use crate::app_config::{AppConfig, ModuleConfig};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn schema_describes_the_config_sections() {
+        let schema = config_schema().expect("schema generates");
+
+        assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+        for section in [
+            "server",
+            "database",
+            "logging",
+            "opentelemetry",
+            "modules",
+            "vendor",
+        ] {
+            assert!(
+                schema["properties"][section].is_object(),
+                "missing section {section}"
+            );
+        }
+        assert!(schema["definitions"]["DbConnConfig"]["properties"]["dsn"].is_object());
+        assert_eq!(
+            schema["definitions"]["DbConnConfig"]["additionalProperties"],
+            false
+        );
+    }
+
+    #[test]
+    fn module_schemas_type_the_config_bag() {
+        let mut schema = config_schema().expect("schema generates");
+        let module_schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "properties": { "retry": { "$ref": "#/$defs/Retry" } },
+            "$defs": {
+                "Retry": { "type": "object", "properties": { "backoff": { "$ref": "#/$defs/Backoff" } } },
+                "Backoff": { "type": "integer" }
+            }
+        });
+
+        add_module_schema(&mut schema, "billing", module_schema).expect("schema added");
+
+        let entry = &schema["properties"]["modules"]["properties"]["billing"];
+        assert_eq!(entry["allOf"][0]["$ref"], "#/definitions/ModuleConfig");
+        let config = &entry["allOf"][1]["properties"]["config"];
+        assert_eq!(
+            config["properties"]["retry"]["$ref"],
+            "#/definitions/billing.Retry"
+        );
+        assert!(config.get("$schema").is_none());
+        assert_eq!(
+            schema["definitions"]["billing.Retry"]["properties"]["backoff"]["$ref"],
+            "#/definitions/billing.Backoff"
+        );
+    }
+
+    #[test]
+    fn reports_module_config_fields_rejected_by_the_schema() {
+        let mut config = AppConfig::default();
+        for (name, module_config) in [
+            (
+                "billing",
+                json!({ "currency": 978, "retries": 3, "extra": true }),
+            ),
+            ("search", json!({ "anything": "goes" })),
+        ] {
+            config.modules.insert(
+                name.to_owned(),
+                ModuleConfig {
+                    config: module_config,
+                    ..ModuleConfig::default()
+                },
+            );
+        }
+        let schemas = BTreeMap::from([(
+            "billing".to_owned(),
+            json!({
+                "type": "object",
+                "required": ["currency", "region"],
+                "additionalProperties": false,
+                "properties": {
+                    "currency": { "type": "string" },
+                    "region": { "type": "string" },
+                    "retries": { "type": "integer", "maximum": 5 }
+                }
+            }),
+        )]);
+
+        let issues = module_config_issues(&config, &schemas).expect("schemas are valid");
+        let mut paths: Vec<_> = issues.iter().map(|issue| issue.path.join(".")).collect();
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                "modules.billing.config",
+                "modules.billing.config",
+                "modules.billing.config.currency",
+            ]
+        );
+        assert!(issues.iter().any(|issue| {
+            issue
+                .to_string()
+                .contains("\"region\" is a required property")
+        }));
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn placeholders_match_any_type() {
+        let mut config = AppConfig::default();
+        config.modules.insert(
+            "billing".to_owned(),
+            ModuleConfig {
+                config: json!({ "port": "${PORT}", "retries": "${RETRIES:-3}", "region": "eu-${ZONE}" }),
+                ..ModuleConfig::default()
+            },
+        );
+        let schemas = BTreeMap::from([(
+            "billing".to_owned(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "port": { "type": "integer" },
+                    "retries": { "type": "integer", "maximum": 5 },
+                    "region": { "enum": ["eu-west", "us-east"] }
+                }
+            }),
+        )]);
+
+        let issues = module_config_issues(&config, &schemas).expect("schemas are valid");
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| issue.path.join("."))
+                .collect::<Vec<_>>(),
+            vec!["modules.billing.config.region"]
+        );
+    }
+}
@@ -0,0 +1,705 @@
+use super::schema;
+use super::yaml::{self, Position, YamlNode, YamlNodeKind};
+use crate::app_config::{AppConfig, DbConnConfig, ModuleConfig, RuntimeKind};
+use crate::common::{self, PathConfigArgs};
+use anyhow::{Context, bail};
+use clap::{Args, ValueEnum};
+use module_parser::{ConfigModule, get_module_name_from_crate};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Top-level keys understood by [`AppConfig`].
+const KNOWN_TOP_LEVEL_KEYS: &[&str] = &[
+    "server",
+    "database",
+    "logging",
+    "opentelemetry",
+    "modules_dir",
+    "modules",
+    "vendor",
+];
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    path_config: PathConfigArgs,
+    /// Output format for the diagnostics
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A single problem found in the config, located by its dotted config path
+/// and, when it can be mapped back to the source, its YAML line and column.
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub path: String,
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// A CLI invocation that fixes the problem, when there is one.
+    pub suggestion: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    file: &'a Path,
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    diagnostics: &'a [Diagnostic],
+}
+
+impl ValidateArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let config_path = self.path_config.resolve_config()?;
+        let raw = fs::read_to_string(&config_path)
+            .with_context(|| format!("can't read config file {}", config_path.display()))?;
+
+        let diagnostics = validate_source(
+            &raw,
+            get_module_name_from_crate(),
+            &self.path_config.config_path()?.display().to_string(),
+        );
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        let warnings = diagnostics.len() - errors;
+
+        match self.format {
+            OutputFormat::Text => print_text(&config_path, &diagnostics, errors, warnings),
+            OutputFormat::Json => {
+                let report = Report {
+                    file: &config_path,
+                    valid: errors == 0,
+                    errors,
+                    warnings,
+                    diagnostics: &diagnostics,
+                };
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report)
+                        .context("failed to serialize diagnostics")?
+                );
+            }
+        }
+
+        if errors > 0 {
+            bail!("config validation failed with {errors} error(s)");
+        }
+        Ok(())
+    }
+}
+
+fn print_text(config_path: &Path, diagnostics: &[Diagnostic], errors: usize, warnings: usize) {
+    for diagnostic in diagnostics {
+        let location = match (diagnostic.line, diagnostic.column) {
+            (Some(line), Some(column)) => format!("{}:{line}:{column}", config_path.display()),
+            _ => config_path.display().to_string(),
+        };
+        println!(
+            "{location}: {}[{}]: {}",
+            diagnostic.severity, diagnostic.code, diagnostic.message
+        );
+        if let Some(suggestion) = &diagnostic.suggestion {
+            println!("  help: {suggestion}");
+        }
+    }
+    if diagnostics.is_empty() {
+        println!("{}: config is valid", config_path.display());
+    } else {
+        println!("{errors} error(s), {warnings} warning(s)");
+    }
+}
+
+/// Validates the raw YAML of a config file. `local_modules` is the result of
+/// discovering the workspace modules; a discovery failure is reported as a
+/// warning and skips the checks that depend on it. `config_arg` is the `-c`
+/// value used in suggested fixes.
+pub fn validate_source(
+    raw: &str,
+    local_modules: anyhow::Result<HashMap<String, ConfigModule>>,
+    config_arg: &str,
+) -> Vec<Diagnostic> {
+    let root = match yaml::parse_document(raw) {
+        Ok(Some(root)) => root,
+        Ok(None) => {
+            let mut validator = Validator::new(None);
+            validator.error("empty-config", &[], "config file is empty".to_owned());
+            return validator.diagnostics;
+        }
+        Err(err) => {
+            return vec![Diagnostic {
+                severity: Severity::Error,
+                code: "yaml-syntax",
+                path: String::new(),
+                message: err.message,
+                line: Some(err.position.line),
+                column: Some(err.position.column),
+                suggestion: None,
+            }];
+        }
+    };
+
+    let mut validator = Validator::new(Some(&root));
+    validator.check_top_level_keys(&root);
+
+    let (mut config, complete) = match serde_saphyr::from_str(raw) {
+        Ok(config) => (config, true),
+        Err(err) => {
+            let location = err.location();
+            validator.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "invalid-config",
+                path: String::new(),
+                message: deserialize_message(&err),
+                line: location.and_then(|l| usize::try_from(l.line()).ok()),
+                column: location.and_then(|l| usize::try_from(l.column()).ok()),
+                suggestion: None,
+            });
+            let Some(config) = salvage_config(raw) else {
+                return validator.diagnostics;
+            };
+            (config, false)
+        }
+    };
+
+    let mut schemas = BTreeMap::new();
+    let not_local = match local_modules {
+        Ok(members) => {
+            match schema::load_module_schemas(&members) {
+                Ok(loaded) => schemas = loaded,
+                Err(err) => validator.error("module-schema", &[], format!("{err:#}")),
+            }
+            Some(common::apply_local_module_metadata(&mut config, members))
+        }
+        Err(err) => {
+            validator.warning(
+                "module-discovery",
+                &[],
+                format!(
+                    "can't discover workspace modules, skipping module resolution: {}",
+                    format!("{err:#}").trim_end()
+                ),
+            );
+            None
+        }
+    };
+
+    validator.check_databases(&config);
+    validator.check_oop_modules(&config);
+    validator.check_module_configs(&config, &schemas);
+    if let Some(not_local) = not_local {
+        validator.check_resolvable(&config, &not_local);
+        // Modules left out of a salvaged config would show up as missing
+        // dependencies and providers.
+        if complete {
+            validator.check_module_graph(&config);
+            validator.check_capabilities(&config, config_arg);
+        }
+    }
+    validator.diagnostics
+}
+
+/// The config without the modules and top-level sections that don't
+/// deserialize, so that one invalid section doesn't hide the problems of the
+/// others. `None` when even the `server` section is invalid.
+fn salvage_config(raw: &str) -> Option<AppConfig> {
+    let Value::Object(mut sections) = serde_saphyr::from_str(raw).ok()? else {
+        return None;
+    };
+    if let Some(Value::Object(modules)) = sections.get_mut("modules") {
+        modules.retain(|_, module| serde_json::from_value::<ModuleConfig>(module.clone()).is_ok());
+    }
+    let server = sections.get("server").cloned();
+    sections.retain(|key, value| {
+        let mut alone = Map::from_iter([(key.clone(), value.clone())]);
+        if let Some(server) = &server {
+            alone.entry("server").or_insert_with(|| server.clone());
+        }
+        serde_json::from_value::<AppConfig>(Value::Object(alone)).is_ok()
+    });
+    serde_json::from_value(Value::Object(sections)).ok()
+}
+
+struct Validator<'a> {
+    root: Option<&'a YamlNode>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    const fn new(root: Option<&'a YamlNode>) -> Self {
+        Self {
+            root,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, severity: Severity, code: &'static str, path: &[&str], message: String) {
+        self.push_with_suggestion(severity, code, path, message, None);
+    }
+
+    fn push_with_suggestion(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        path: &[&str],
+        message: String,
+        suggestion: Option<String>,
+    ) {
+        let position = self
+            .root
+            .filter(|_| !path.is_empty())
+            .map(|root| root.locate(path));
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            path: path.join("."),
+            message,
+            line: position.map(|p: Position| p.line),
+            column: position.map(|p| p.column),
+            suggestion,
+        });
+    }
+
+    fn error(&mut self, code: &'static str, path: &[&str], message: String) {
+        self.push(Severity::Error, code, path, message);
+    }
+
+    fn warning(&mut self, code: &'static str, path: &[&str], message: String) {
+        self.push(Severity::Warning, code, path, message);
+    }
+
+    fn check_top_level_keys(&mut self, root: &YamlNode) {
+        if !matches!(root.kind, YamlNodeKind::Mapping(_)) {
+            self.error(
+                "invalid-config",
+                &[],
+                "config must be a YAML mapping".to_owned(),
+            );
+            return;
+        }
+        for (key, _, _) in root.entries() {
+            if !KNOWN_TOP_LEVEL_KEYS.contains(&key) {
+                self.error(
+                    "unknown-key",
+                    &[key],
+                    format!(
+                        "unknown top-level key '{key}'. Expected one of: {}",
+                        KNOWN_TOP_LEVEL_KEYS.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_databases(&mut self, config: &AppConfig) {
+        let servers = config.database.as_ref().map(|db| &db.servers);
+        for (name, conn) in servers.into_iter().flatten() {
+            if !has_connection_target(conn) {
+                self.error(
+                    "db-missing-connection",
+                    &["database", "servers", name],
+                    format!("database server '{name}' has neither `dsn`, `host` nor a sqlite `file`/`path`"),
+                );
+            }
+        }
+
+        for (name, module) in &config.modules {
+            let Some(conn) = &module.database else {
+                continue;
+            };
+            if let Some(server) = &conn.server {
+                if !servers.is_some_and(|servers| servers.contains_key(server)) {
+                    self.error(
+                        "db-unknown-server",
+                        &["modules", name, "database", "server"],
+                        format!(
+                            "module '{name}' references database server '{server}', which is not defined in `database.servers`"
+                        ),
+                    );
+                }
+            } else if !has_connection_target(conn) {
+                self.error(
+                    "db-missing-connection",
+                    &["modules", name, "database"],
+                    format!(
+                        "module '{name}' database has neither `server`, `dsn`, `host` nor a sqlite `file`/`path`"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_oop_modules(&mut self, config: &AppConfig) {
+        for (name, module) in &config.modules {
+            let Some(runtime) = &module.runtime else {
+                continue;
+            };
+            if !matches!(runtime.mod_type, RuntimeKind::Oop) {
+                continue;
+            }
+            let Some(execution) = &runtime.execution else {
+                self.error(
+                    "oop-missing-execution",
+                    &["modules", name, "runtime"],
+                    format!("out-of-process module '{name}' has no `runtime.execution` section"),
+                );
+                continue;
+            };
+            if !executable_exists(&execution.executable_path) {
+                self.error(
+                    "oop-executable-not-found",
+                    &["modules", name, "runtime", "execution", "executable_path"],
+                    format!(
+                        "executable '{}' of out-of-process module '{name}' doesn't exist",
+                        execution.executable_path
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_module_configs(&mut self, config: &AppConfig, schemas: &BTreeMap<String, Value>) {
+        match schema::module_config_issues(config, schemas) {
+            Ok(issues) => {
+                for issue in issues {
+                    let path: Vec<&str> = issue.path.iter().map(String::as_str).collect();
+                    self.error("module-config", &path, issue.message);
+                }
+            }
+            Err(err) => self.error("module-schema", &[], format!("{err:#}")),
+        }
+    }
+
+    fn check_resolvable(&mut self, config: &AppConfig, not_local: &[String]) {
+        for name in not_local {
+            let Some(module) = config.modules.get(name) else {
+                continue;
+            };
+            if module
+                .runtime
+                .as_ref()
+                .is_some_and(|runtime| matches!(runtime.mod_type, RuntimeKind::Oop))
+            {
+                continue;
+            }
+            let metadata = module.metadata.as_ref();
+            let Some(package) = metadata.and_then(|m| m.package.as_deref()) else {
+                self.error(
+                    "module-unresolved",
+                    &["modules", name],
+                    format!(
+                        "module '{name}' is not in the workspace and has no `metadata.package` to fetch it from the registry"
+                    ),
+                );
+                continue;
+            };
+            if metadata.is_some_and(|m| m.version.is_none() && m.path.is_none()) {
+                self.error(
+                    "module-unresolved",
+                    &["modules", name, "metadata"],
+                    format!(
+                        "module '{name}' is not in the workspace and package '{package}' has neither `version` nor `path`"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_module_graph(&mut self, config: &AppConfig) {
+        for issue in config.module_graph_issues() {
+            self.error("module-graph", &["modules", &issue.module], issue.message);
+        }
+    }
+
+    fn check_capabilities(&mut self, config: &AppConfig, config_arg: &str) {
+        for issue in config.capability_issues() {
+            let suggestion = issue.fix_command(config_arg);
+            self.push_with_suggestion(
+                Severity::Error,
+                "module-capability",
+                &["modules", &issue.module],
+                issue.message,
+                Some(suggestion),
+            );
+        }
+    }
+}
+
+/// The deserializer error without its source snippet and position prefix,
+/// since the position is reported separately.
+fn deserialize_message(err: &serde_saphyr::Error) -> String {
+    let message = err.without_snippet().to_string();
+    let message = message.strip_prefix("error: ").unwrap_or(&message);
+    let message = match message.split_once(": ") {
+        Some((prefix, rest)) if prefix.starts_with("line ") => rest,
+        _ => message,
+    };
+    message.trim().to_owned()
+}
+
+const fn has_connection_target(conn: &DbConnConfig) -> bool {
+    conn.dsn.is_some() || conn.host.is_some() || conn.file.is_some() || conn.path.is_some()
+}
+
+/// Mirrors how the executable is resolved at startup: `~` expands to the
+/// home directory and bare names are looked up in `PATH`.
+fn executable_exists(executable: &str) -> bool {
+    let path = match executable.strip_prefix("~/") {
+        Some(rest) => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(rest),
+            None => return false,
+        },
+        None => PathBuf::from(executable),
+    };
+
+    if path.components().count() > 1 || path.is_absolute() {
+        return path.is_file();
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(&path).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Severity, validate_source};
+    use module_parser::{Capability, ConfigModule, ConfigModuleMetadata};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn local(names: &[&str]) -> HashMap<String, ConfigModule> {
+        names
+            .iter()
+            .map(|name| {
+                (
+                    (*name).to_owned(),
+                    ConfigModule {
+                        metadata: ConfigModuleMetadata {
+                            package: Some(format!("cf-{name}")),
+                            version: Some("0.1.0".to_owned()),
+                            ..ConfigModuleMetadata::default()
+                        },
+                        config_schema: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(&str, &str)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.code, d.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_diagnostics() {
+        let raw = "server:
+  home_dir: /tmp
+database:
+  servers:
+    main:
+      host: localhost
+modules:
+  demo:
+    database:
+      server: main
+";
+        assert!(validate_source(raw, Ok(local(&["demo"])), "app.yml").is_empty());
+    }
+
+    #[test]
+    fn reports_every_problem_with_positions() {
+        let raw = "server:
+  home_dir: /tmp
+databse: {}
+database:
+  servers:
+    empty:
+      port: 5432
+modules:
+  demo:
+    database:
+      server: missing
+  remote: {}
+  worker:
+    runtime:
+      type: oop
+      execution:
+        executable_path: /definitely/not/here
+";
+        let diagnostics = validate_source(raw, Ok(local(&["demo"])), "app.yml");
+
+        assert_eq!(
+            codes(&diagnostics),
+            vec![
+                ("unknown-key", "databse"),
+                ("db-missing-connection", "database.servers.empty"),
+                ("db-unknown-server", "modules.demo.database.server"),
+                (
+                    "oop-executable-not-found",
+                    "modules.worker.runtime.execution.executable_path"
+                ),
+                ("module-unresolved", "modules.remote"),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(3), Some(1))
+        );
+        assert_eq!(
+            (diagnostics[2].line, diagnostics[2].column),
+            (Some(11), Some(7))
+        );
+    }
+
+    #[test]
+    fn reports_deserialize_errors_without_snippet() {
+        let raw = "server:\n  home_dir: /tmp\ndatabase:\n  servers:\n    main:\n      hots: x\n";
+        let diagnostics = validate_source(raw, Ok(local(&[])), "app.yml");
+
+        assert_eq!(codes(&diagnostics), vec![("invalid-config", "")]);
+        assert!(diagnostics[0].message.starts_with("unknown field `hots`"));
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(6), Some(7))
+        );
+    }
+
+    #[test]
+    fn checks_the_valid_sections_when_deserialization_fails() {
+        let raw = "server:
+  home_dir: /tmp
+databse: {}
+database:
+  servers:
+    empty:
+      port: 5432
+modules:
+  demo:
+    database:
+      server: missing
+  broken:
+    metadata: 5
+";
+        let diagnostics = validate_source(raw, Ok(local(&["demo"])), "app.yml");
+
+        assert_eq!(
+            codes(&diagnostics),
+            vec![
+                ("unknown-key", "databse"),
+                ("invalid-config", ""),
+                ("db-missing-connection", "database.servers.empty"),
+                ("db-unknown-server", "modules.demo.database.server"),
+            ]
+        );
+        assert_eq!(diagnostics[1].line, Some(13));
+    }
+
+    #[test]
+    fn capability_findings_suggest_a_fix() {
+        let raw = "server:\n  home_dir: /tmp\nmodules:\n  demo: {}\n";
+        let mut modules = local(&["demo"]);
+        if let Some(demo) = modules.get_mut("demo") {
+            demo.metadata.capabilities = vec![Capability::Rest];
+        }
+
+        let diagnostics = validate_source(raw, Ok(modules), "app.yml");
+
+        assert_eq!(
+            codes(&diagnostics),
+            vec![("module-capability", "modules.demo")]
+        );
+        assert_eq!(
+            diagnostics[0].suggestion.as_deref(),
+            Some(
+                "cargo cyberfabric config mod add api-gateway --package cf-api-gateway --module-version <VERSION> -c app.yml"
+            )
+        );
+    }
+
+    #[test]
+    fn module_config_is_checked_against_the_module_schema() {
+        let raw = "server:
+  home_dir: /tmp
+modules:
+  demo:
+    config:
+      retries: many
+";
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let schema_path = temp_dir.path().join("config.schema.json");
+        fs::write(
+            &schema_path,
+            r#"{"type": "object", "properties": {"retries": {"type": "integer"}}}"#,
+        )
+        .expect("write schema");
+        let mut modules = local(&["demo"]);
+        if let Some(demo) = modules.get_mut("demo") {
+            demo.config_schema = Some(schema_path);
+        }
+
+        let diagnostics = validate_source(raw, Ok(modules), "app.yml");
+
+        assert_eq!(
+            codes(&diagnostics),
+            vec![("module-config", "modules.demo.config.retries")]
+        );
+        assert!(
+            diagnostics[0]
+                .message
+                .contains("is not of type \"integer\"")
+        );
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(6), Some(7))
+        );
+    }
+
+    #[test]
+    fn reports_yaml_syntax_errors() {
+        let diagnostics = validate_source("server:\n  home_dir: [\n", Ok(local(&[])), "app.yml");
+        assert_eq!(codes(&diagnostics), vec![("yaml-syntax", "")]);
+        assert!(diagnostics[0].line.is_some());
+    }
+
+    #[test]
+    fn discovery_failure_is_a_warning() {
+        let raw = "server:\n  home_dir: /tmp\nmodules:\n  remote: {}\n";
+        let diagnostics = validate_source(raw, Err(anyhow::anyhow!("no workspace")), "app.yml");
+        assert_eq!(codes(&diagnostics), vec![("module-discovery", "")]);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+}
@@ -0,0 +1,118 @@
+use super::document::ConfigDocument;
+use super::{overlay, read_config, write_config};
+use crate::app_config::AppConfig;
+use crate::common::PathConfigArgs;
+use anyhow::{Context, bail};
+use clap::Args;
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(Args)]
+pub struct GetArgs {
+    #[command(flatten)]
+    path_config: PathConfigArgs,
+    /// Dotted path of the value, such as `modules.users.config.batch_size`
+    #[arg(value_name = "KEY")]
+    key: String,
+}
+
+impl GetArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let config_path = self.path_config.resolve_config()?;
+        let path = parse_path(&self.key)?;
+        let config: Value = serde_saphyr::from_str(&read_config(&config_path)?)
+            .with_context(|| format!("config not valid at {}", config_path.display()))?;
+        let value = path
+            .iter()
+            .try_fold(&config, |node, segment| match node {
+                Value::Object(map) => map.get(*segment),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            })
+            .with_context(|| format!("'{}' is not set in {}", self.key, config_path.display()))?;
+
+        match value {
+            Value::String(value) => println!("{value}"),
+            Value::Array(_) | Value::Object(_) => print!("{}", overlay::to_yaml(value)?),
+            _ => println!("{value}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct SetArgs {
+    #[command(flatten)]
+    path_config: PathConfigArgs,
+    /// Dotted path of the value, such as `modules.users.config.batch_size`
+    #[arg(value_name = "KEY")]
+    key: String,
+    /// YAML value: `10` and `true` are a number and a boolean, quote them to get strings
+    value: String,
+}
+
+impl SetArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let config_path = self.path_config.resolve_config()?;
+        let path = parse_path(&self.key)?;
+        let value = serde_saphyr::from_str::<Value>(&self.value)
+            .unwrap_or_else(|_| Value::String(self.value.clone()));
+
+        let mut document = ConfigDocument::new(read_config(&config_path)?);
+        document.set(&path, &value)?;
+        save_document(&config_path, document)
+    }
+}
+
+#[derive(Args)]
+pub struct UnsetArgs {
+    #[command(flatten)]
+    path_config: PathConfigArgs,
+    /// Dotted path of the entry to remove, such as `modules.users.config.batch_size`
+    #[arg(value_name = "KEY")]
+    key: String,
+}
+
+impl UnsetArgs {
+    pub(super) fn run(&self) -> anyhow::Result<()> {
+        let config_path = self.path_config.resolve_config()?;
+        let path = parse_path(&self.key)?;
+
+        let mut document = ConfigDocument::new(read_config(&config_path)?);
+        if !document.unset(&path)? {
+            bail!("'{}' is not set in {}", self.key, config_path.display());
+        }
+        save_document(&config_path, document)
+    }
+}
+
+fn parse_path(path: &str) -> anyhow::Result<Vec<&str>> {
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        bail!("invalid config path '{path}', expected dotted keys such as `server.home_dir`");
+    }
+    Ok(segments)
+}
+
+/// Writes the edited document, unless the edit breaks the config.
+fn save_document(path: &Path, document: ConfigDocument) -> anyhow::Result<()> {
+    let text = document.into_text();
+    serde_saphyr::from_str::<AppConfig>(&text)
+        .context("the change would make the config invalid, it was not saved")?;
+    write_config(path, &text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_path;
+
+    #[test]
+    fn parses_dotted_paths() {
+        assert_eq!(
+            parse_path("modules.api-gateway.config.cors.0").expect("valid path"),
+            ["modules", "api-gateway", "config", "cors", "0"]
+        );
+        assert!(parse_path("modules..config").is_err());
+        assert!(parse_path("").is_err());
+    }
+}
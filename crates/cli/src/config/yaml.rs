@@ -0,0 +1,266 @@
+use saphyr_parser_bw::{Event, Marker, Parser, Span};
+use serde::Serialize;
+use std::fmt;
+
+/// 1-indexed line/column position inside a YAML document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl From<&Marker> for Position {
+    fn from(marker: &Marker) -> Self {
+        Self {
+            line: marker.line(),
+            column: marker.col() + 1,
+        }
+    }
+}
+
+/// YAML syntax error with the position where the parser gave up.
+#[derive(Debug)]
+pub struct YamlError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.message, self.position.line, self.position.column
+        )
+    }
+}
+
+impl std::error::Error for YamlError {}
+
+/// A YAML node with the source span it was parsed from.
+///
+/// This is intentionally a thin tree over the parser events: scalars are kept
+/// as raw strings and mappings keep their key order, so callers can map
+/// config paths back to source positions.
+#[derive(Debug)]
+pub struct YamlNode {
+    pub kind: YamlNodeKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum YamlNodeKind {
+    Scalar(String),
+    Sequence(Vec<YamlNode>),
+    Mapping(Vec<(YamlNode, YamlNode)>),
+    Alias,
+}
+
+impl YamlNode {
+    #[must_use]
+    pub fn position(&self) -> Position {
+        Position::from(&self.span.start)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.kind {
+            YamlNodeKind::Scalar(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Iterates `(key, key node, value node)` for mapping nodes with scalar keys.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Self, &Self)> {
+        let entries = match &self.kind {
+            YamlNodeKind::Mapping(entries) => entries.as_slice(),
+            _ => &[],
+        };
+        entries
+            .iter()
+            .filter_map(|(key, value)| key.as_str().map(|k| (k, key, value)))
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        self.entries()
+            .find_map(|(k, _, value)| (k == key).then_some(value))
+    }
+
+    #[must_use]
+    pub fn key_node(&self, key: &str) -> Option<&Self> {
+        self.entries()
+            .find_map(|(k, key_node, _)| (k == key).then_some(key_node))
+    }
+
+    /// Resolves a path of mapping keys. Numeric segments index into sequences.
+    #[must_use]
+    pub fn at<S: AsRef<str>>(&self, path: &[S]) -> Option<&Self> {
+        path.iter().try_fold(self, |node, segment| {
+            let segment = segment.as_ref();
+            match &node.kind {
+                YamlNodeKind::Sequence(items) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| items.get(index)),
+                _ => node.get(segment),
+            }
+        })
+    }
+
+    /// Best-effort position for a path: the deepest node that exists, pointing
+    /// at the key of the last resolved mapping entry when possible.
+    #[must_use]
+    pub fn locate<S: AsRef<str>>(&self, path: &[S]) -> Position {
+        let mut node = self;
+        let mut position = self.position();
+        for segment in path {
+            let segment = segment.as_ref();
+            if let Some(key) = node.key_node(segment) {
+                position = key.position();
+            }
+            let Some(next) = node.at(&[segment]) else {
+                break;
+            };
+            if !matches!(node.kind, YamlNodeKind::Mapping(_)) {
+                position = next.position();
+            }
+            node = next;
+        }
+        position
+    }
+}
+
+/// Parses the first document of `raw`. Returns `None` for an empty stream.
+pub fn parse_document(raw: &str) -> Result<Option<YamlNode>, YamlError> {
+    let mut parser = Parser::new_from_str(raw);
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut last = Marker::default();
+
+    while let Some(next) = parser.next_event() {
+        let (event, span) = next.map_err(|err| YamlError {
+            message: err.info().to_owned(),
+            position: err.marker().into(),
+        })?;
+        last = span.end;
+        let node = match event {
+            Event::Scalar(value, ..) => YamlNode {
+                kind: YamlNodeKind::Scalar(value.into_owned()),
+                span,
+            },
+            Event::Alias(_) => YamlNode {
+                kind: YamlNodeKind::Alias,
+                span,
+            },
+            Event::SequenceStart(..) => {
+                stack.push(Frame::Sequence(span, Vec::new()));
+                continue;
+            }
+            Event::MappingStart(..) => {
+                stack.push(Frame::Mapping(span, Vec::new(), None));
+                continue;
+            }
+            Event::SequenceEnd => match stack.pop() {
+                Some(Frame::Sequence(start, items)) => YamlNode {
+                    kind: YamlNodeKind::Sequence(items),
+                    span: Span::new(start.start, span.end),
+                },
+                _ => return Err(unbalanced("sequence", &span.start)),
+            },
+            Event::MappingEnd => match stack.pop() {
+                Some(Frame::Mapping(start, entries, _)) => YamlNode {
+                    kind: YamlNodeKind::Mapping(entries),
+                    span: Span::new(start.start, span.end),
+                },
+                _ => return Err(unbalanced("mapping", &span.start)),
+            },
+            Event::DocumentEnd => break,
+            _ => continue,
+        };
+
+        match stack.last_mut() {
+            None => return Ok(Some(node)),
+            Some(Frame::Sequence(_, items)) => items.push(node),
+            Some(Frame::Mapping(_, entries, pending_key)) => match pending_key.take() {
+                Some(key) => entries.push((key, node)),
+                None => *pending_key = Some(node),
+            },
+        }
+    }
+
+    if stack.is_empty() {
+        Ok(None)
+    } else {
+        Err(YamlError {
+            message: "unexpected end of YAML document".to_owned(),
+            position: (&last).into(),
+        })
+    }
+}
+
+fn unbalanced(kind: &str, at: &Marker) -> YamlError {
+    YamlError {
+        message: format!("unbalanced YAML {kind}"),
+        position: at.into(),
+    }
+}
+
+enum Frame {
+    Sequence(Span, Vec<YamlNode>),
+    Mapping(Span, Vec<(YamlNode, YamlNode)>, Option<YamlNode>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Position, parse_document};
+
+    const CONFIG: &str = "server:
+  home_dir: /tmp
+modules:
+  demo:
+    config:
+      items: [a, b]
+";
+
+    #[test]
+    fn resolves_paths_and_positions() {
+        let root = parse_document(CONFIG)
+            .expect("yaml should parse")
+            .expect("document should exist");
+
+        assert_eq!(
+            root.at(&["server", "home_dir"]).and_then(|n| n.as_str()),
+            Some("/tmp")
+        );
+        assert_eq!(
+            root.at(&["modules", "demo", "config", "items", "1"])
+                .and_then(|n| n.as_str()),
+            Some("b")
+        );
+        assert_eq!(
+            root.locate(&["modules", "demo"]),
+            Position { line: 4, column: 3 }
+        );
+        assert_eq!(
+            root.locate(&["modules", "demo", "missing"]),
+            Position { line: 4, column: 3 }
+        );
+    }
+
+    #[test]
+    fn empty_stream_has_no_document() {
+        assert!(parse_document("").expect("empty yaml is valid").is_none());
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_position() {
+        let err = parse_document("a: b\nc: [1, 2\n").expect_err("unclosed flow should fail");
+        assert_eq!(err.position.line, 2);
+    }
+}
@@ -0,0 +1,263 @@
+use super::DockerBuildArg;
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Tool that builds the image. Every tool gets the same Dockerfile, build
+/// context and build args.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildTool {
+    Docker,
+    Podman,
+    /// Daemonless, for rootless CI.
+    Buildah,
+}
+
+impl BuildTool {
+    const fn program(self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+            Self::Buildah => "buildah",
+        }
+    }
+
+    /// The first of docker, podman and buildah found in `PATH`.
+    pub(super) fn detect() -> anyhow::Result<Self> {
+        let paths = env::var_os("PATH").unwrap_or_default();
+        [Self::Docker, Self::Podman, Self::Buildah]
+            .into_iter()
+            .find(|tool| env::split_paths(&paths).any(|dir| dir.join(tool.program()).is_file()))
+            .context("none of docker, podman or buildah was found in PATH; install one or pass --builder")
+    }
+}
+
+impl fmt::Display for BuildTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.program())
+    }
+}
+
+/// An image build, independent of the tool that runs it.
+pub(super) struct ImageBuild {
+    pub(super) context: PathBuf,
+    pub(super) dockerfile: Option<PathBuf>,
+    pub(super) build_args: Vec<DockerBuildArg>,
+    /// Never empty.
+    pub(super) tags: Vec<String>,
+    pub(super) platforms: Vec<String>,
+    pub(super) push: bool,
+    pub(super) output: Option<String>,
+}
+
+impl ImageBuild {
+    /// The commands that build the image with `tool`, then push it when asked to.
+    pub(super) fn commands(&self, tool: BuildTool) -> anyhow::Result<Vec<Command>> {
+        match tool {
+            BuildTool::Docker => Ok(vec![self.docker_command()?]),
+            BuildTool::Podman | BuildTool::Buildah => Ok(self.oci_commands(tool)),
+        }
+    }
+
+    /// Multi-platform builds, pushes and custom outputs need buildx.
+    const fn needs_buildx(&self) -> bool {
+        !self.platforms.is_empty() || self.push || self.output.is_some()
+    }
+
+    fn docker_command(&self) -> anyhow::Result<Command> {
+        let mut command = Command::new(BuildTool::Docker.program());
+        if self.needs_buildx() {
+            command.arg("buildx");
+        }
+        command.arg("build");
+        self.add_common_args(&mut command);
+        for tag in &self.tags {
+            command.arg("--tag").arg(tag);
+        }
+        if !self.platforms.is_empty() {
+            command.arg("--platform").arg(self.platforms.join(","));
+        }
+        if self.push {
+            command.arg("--push");
+        } else if let Some(output) = &self.output {
+            command.arg("--output").arg(output);
+        } else if self.platforms.len() > 1 {
+            bail!(
+                "the local image store holds a single platform; pass --push or --output to build for {}",
+                self.platforms.join(", ")
+            );
+        } else if self.needs_buildx() {
+            // Buildx leaves the result in its build cache unless told otherwise.
+            command.arg("--load");
+        }
+
+        command.arg(".");
+        command.current_dir(&self.context);
+        Ok(command)
+    }
+
+    /// Podman and Buildah keep several platforms in a local manifest list,
+    /// named by the first tag, and push in a separate step.
+    fn oci_commands(&self, tool: BuildTool) -> Vec<Command> {
+        let program = tool.program();
+        let manifest = (self.platforms.len() > 1).then(|| &self.tags[0]);
+
+        let mut build = Command::new(program);
+        build.arg("build");
+        self.add_common_args(&mut build);
+        if let Some(manifest) = manifest {
+            build.arg("--manifest").arg(manifest);
+        } else {
+            for tag in &self.tags {
+                build.arg("--tag").arg(tag);
+            }
+        }
+        if !self.platforms.is_empty() {
+            build.arg("--platform").arg(self.platforms.join(","));
+        }
+        if let Some(output) = &self.output {
+            build.arg("--output").arg(output);
+        }
+        // Unlike BuildKit, which reads it from the build arg, these need the
+        // timestamp passed to clamp the image and file times.
+        if let Some(epoch) = self.source_date_epoch() {
+            build.arg("--timestamp").arg(epoch);
+        }
+        build.arg(".");
+        build.current_dir(&self.context);
+
+        let mut commands = vec![build];
+        if self.push {
+            commands.extend(self.tags.iter().map(|tag| {
+                let mut push = Command::new(program);
+                if let Some(manifest) = manifest {
+                    push.args(["manifest", "push", "--all", manifest])
+                        .arg(format!("docker://{tag}"));
+                } else {
+                    push.arg("push").arg(tag);
+                }
+                push
+            }));
+        }
+        commands
+    }
+
+    /// The last `SOURCE_DATE_EPOCH` build arg, the one the build sees.
+    fn source_date_epoch(&self) -> Option<&str> {
+        self.build_args
+            .iter()
+            .rev()
+            .find(|arg| arg.key == "SOURCE_DATE_EPOCH")
+            .map(|arg| arg.value.as_str())
+    }
+
+    fn add_common_args(&self, command: &mut Command) {
+        for arg in &self.build_args {
+            add_build_arg(command, &arg.key, &arg.value);
+        }
+        if let Some(dockerfile) = &self.dockerfile {
+            command.arg("--file").arg(dockerfile);
+        }
+    }
+}
+
+fn add_build_arg<T>(command: &mut Command, key: &str, value: T)
+where
+    T: AsRef<std::ffi::OsStr>,
+{
+    command
+        .arg("--build-arg")
+        .arg(format!("{key}={}", value.as_ref().to_string_lossy()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildTool, ImageBuild};
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn image_build(platforms: &[&str]) -> ImageBuild {
+        ImageBuild {
+            context: PathBuf::from("/workspace"),
+            dockerfile: None,
+            build_args: vec!["ARTIFACT_NAME=demo".parse().expect("arg")],
+            tags: vec!["acme/demo:1.0".to_owned(), "acme/demo:latest".to_owned()],
+            platforms: platforms.iter().map(ToString::to_string).collect(),
+            push: false,
+            output: None,
+        }
+    }
+
+    fn command_lines(build: &ImageBuild, tool: BuildTool) -> anyhow::Result<Vec<String>> {
+        Ok(build
+            .commands(tool)?
+            .iter()
+            .map(|command: &Command| {
+                std::iter::once(command.get_program())
+                    .chain(command.get_args())
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect())
+    }
+
+    #[test]
+    fn docker_builds_use_buildx_for_platforms_and_pushes() -> anyhow::Result<()> {
+        assert_eq!(
+            command_lines(&image_build(&[]), BuildTool::Docker)?,
+            [
+                "docker build --build-arg ARTIFACT_NAME=demo --tag acme/demo:1.0 --tag acme/demo:latest ."
+            ]
+        );
+
+        let mut build = image_build(&["linux/amd64", "linux/arm64"]);
+        assert!(build.commands(BuildTool::Docker).is_err());
+        build.push = true;
+        assert_eq!(
+            command_lines(&build, BuildTool::Docker)?,
+            [
+                "docker buildx build --build-arg ARTIFACT_NAME=demo --tag acme/demo:1.0 --tag acme/demo:latest --platform linux/amd64,linux/arm64 --push ."
+            ]
+        );
+
+        let build = image_build(&["linux/arm64"]);
+        assert!(command_lines(&build, BuildTool::Docker)?[0].ends_with(" --load ."));
+        Ok(())
+    }
+
+    #[test]
+    fn podman_and_buildah_build_manifest_lists_and_push_separately() -> anyhow::Result<()> {
+        let mut build = image_build(&["linux/amd64", "linux/arm64"]);
+        build.push = true;
+
+        assert_eq!(
+            command_lines(&build, BuildTool::Buildah)?,
+            [
+                "buildah build --build-arg ARTIFACT_NAME=demo --manifest acme/demo:1.0 --platform linux/amd64,linux/arm64 .",
+                "buildah manifest push --all acme/demo:1.0 docker://acme/demo:1.0",
+                "buildah manifest push --all acme/demo:1.0 docker://acme/demo:latest",
+            ]
+        );
+
+        let mut build = image_build(&[]);
+        build.push = true;
+        build
+            .build_args
+            .push("SOURCE_DATE_EPOCH=1700000000".parse().expect("arg"));
+        assert_eq!(
+            command_lines(&build, BuildTool::Podman)?,
+            [
+                "podman build --build-arg ARTIFACT_NAME=demo --build-arg SOURCE_DATE_EPOCH=1700000000 --tag acme/demo:1.0 --tag acme/demo:latest --timestamp 1700000000 .",
+                "podman push acme/demo:1.0",
+                "podman push acme/demo:latest",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+//! Kubernetes manifests, or a Helm chart, for the image `deploy` builds.
+
+use crate::app_config::{AppConfig, ModuleConfig};
+use crate::config::env::placeholders;
+use crate::config::overlay::to_yaml;
+use anyhow::Context;
+use module_parser::Capability;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Where the Dockerfile expects the config, as `CF_CLI_CONFIG` points to it.
+pub(super) const CONFIG_DIR: &str = "/app/config";
+
+/// A port the server listens on, named after the protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Port {
+    pub(super) name: &'static str,
+    pub(super) number: u16,
+}
+
+/// The deployed server: its name, image and config.
+pub(super) struct Workload<'a> {
+    name: String,
+    image: &'a str,
+    source: String,
+    config_ext: &'a str,
+    /// Variables of the config's `${VAR}` placeholders, each with whether
+    /// every reference to it has a default.
+    variables: BTreeMap<String, bool>,
+    ports: Vec<Port>,
+}
+
+impl<'a> Workload<'a> {
+    /// `ports` are the server's ports, see [`ports`].
+    pub(super) fn new(
+        name: &str,
+        image: &'a str,
+        config_path: &'a Path,
+        config_ext: &'a str,
+        ports: Vec<Port>,
+    ) -> anyhow::Result<Self> {
+        let source = fs::read_to_string(config_path)
+            .with_context(|| format!("can't read config file {}", config_path.display()))?;
+        Self::from_config(name, image, source, config_ext, ports)
+            .with_context(|| format!("config not valid at {}", config_path.display()))
+    }
+
+    fn from_config(
+        name: &str,
+        image: &'a str,
+        source: String,
+        config_ext: &'a str,
+        ports: Vec<Port>,
+    ) -> anyhow::Result<Self> {
+        let raw_config: Value = serde_saphyr::from_str(&source)?;
+        Ok(Self {
+            name: object_name(name),
+            image,
+            variables: placeholders(&raw_config),
+            ports,
+            source,
+            config_ext,
+        })
+    }
+
+    fn config_file(&self) -> String {
+        format!("config.{}", self.config_ext)
+    }
+
+    fn labels(&self) -> Value {
+        json!({ "app.kubernetes.io/name": self.name })
+    }
+
+    fn secret_name(&self) -> String {
+        format!("{}-env", self.name)
+    }
+
+    fn config_map(&self) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": format!("{}-config", self.name), "labels": self.labels() },
+            "data": { self.config_file(): self.source },
+        })
+    }
+
+    /// Variables whose every reference has a default, and the others.
+    fn split_variables(&self) -> (Vec<&str>, Vec<&str>) {
+        let (mut defaulted, mut required) = (Vec::new(), Vec::new());
+        for (name, has_default) in &self.variables {
+            if *has_default {
+                defaulted.push(name.as_str());
+            } else {
+                required.push(name.as_str());
+            }
+        }
+        (defaulted, required)
+    }
+
+    /// An example of the secret the deployment reads the placeholders from.
+    /// It isn't applied with the manifests, so that it never overwrites the
+    /// values of an existing secret; defaulted variables are left out, so
+    /// their defaults apply until they are set.
+    fn secret_example(&self) -> anyhow::Result<Option<String>> {
+        if self.variables.is_empty() {
+            return Ok(None);
+        }
+        let (defaulted, required) = self.split_variables();
+        let data: BTreeMap<_, _> = required.iter().map(|name| (*name, "")).collect();
+        let mut example = format!(
+            "# Fill in the values and apply this file, or create the secret directly:\n#   kubectl create secret generic {} {}\n",
+            self.secret_name(),
+            required
+                .iter()
+                .map(|name| format!("--from-literal={name}=..."))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        if !defaulted.is_empty() {
+            _ = writeln!(
+                example,
+                "# Optional, the config has defaults for them: {}",
+                defaulted.join(", ")
+            );
+        }
+        example.push_str(&to_yaml(&json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": { "name": self.secret_name(), "labels": self.labels() },
+            "type": "Opaque",
+            "stringData": data,
+        }))?);
+        Ok(Some(example))
+    }
+
+    /// The chart's secret, made from `.Values.env` unless `.Values.existingSecret`
+    /// names one; rendering fails while a variable without default is unset.
+    fn secret_template(&self) -> Option<String> {
+        if self.variables.is_empty() {
+            return None;
+        }
+        let (_, required) = self.split_variables();
+        let name = &self.name;
+        let mut template = "{{- if not .Values.existingSecret }}\n".to_owned();
+        for variable in required {
+            _ = writeln!(
+                template,
+                "{{{{- $_ := required \"set env.{variable} or existingSecret\" (index .Values.env \"{variable}\") }}}}"
+            );
+        }
+        _ = write!(
+            template,
+            "{{{{- if .Values.env }}}}\napiVersion: v1\nkind: Secret\nmetadata:\n  name: {name}-env\n  labels:\n    app.kubernetes.io/name: {name}\ntype: Opaque\nstringData:\n{{{{- range $key, $value := .Values.env }}}}\n  {{{{ $key }}}}: {{{{ $value | quote }}}}\n{{{{- end }}}}\n{{{{- end }}}}\n{{{{- end }}}}\n"
+        );
+        Some(template)
+    }
+
+    fn deployment(&self, image: &str, secret_name: &str) -> Value {
+        let mut env = vec![json!({
+            "name": "CF_CLI_CONFIG",
+            "value": format!("{CONFIG_DIR}/{}", self.config_file()),
+        })];
+        env.extend(self.variables.iter().map(|(name, defaulted)| {
+            json!({
+                "name": name,
+                "valueFrom": { "secretKeyRef": {
+                    "name": secret_name,
+                    "key": name,
+                    "optional": defaulted,
+                }},
+            })
+        }));
+        let mut container = json!({
+            "name": self.name,
+            "image": image,
+            "env": env,
+            "ports": self.ports
+                .iter()
+                .map(|port| json!({ "name": port.name, "containerPort": port.number }))
+                .collect::<Vec<_>>(),
+            "volumeMounts": [{ "name": "config", "mountPath": CONFIG_DIR, "readOnly": true }],
+        });
+        // Probes only check that the server accepts connections, which holds
+        // whatever health routes the modules serve.
+        if let Some(port) = self.ports.first() {
+            container["livenessProbe"] = json!({
+                "tcpSocket": { "port": port.name },
+                "initialDelaySeconds": 10,
+                "periodSeconds": 10,
+            });
+            container["readinessProbe"] = json!({
+                "tcpSocket": { "port": port.name },
+                "periodSeconds": 5,
+            });
+        }
+        json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": self.name, "labels": self.labels() },
+            "spec": {
+                "replicas": 1,
+                "selector": { "matchLabels": self.labels() },
+                "template": {
+                    "metadata": { "labels": self.labels() },
+                    "spec": {
+                        "containers": [container],
+                        "volumes": [{
+                            "name": "config",
+                            "configMap": { "name": format!("{}-config", self.name) },
+                        }],
+                    },
+                },
+            },
+        })
+    }
+
+    fn service(&self) -> Option<Value> {
+        if self.ports.is_empty() {
+            return None;
+        }
+        Some(json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": self.name, "labels": self.labels() },
+            "spec": {
+                "selector": self.labels(),
+                "ports": self.ports
+                    .iter()
+                    .map(|port| json!({
+                        "name": port.name,
+                        "port": port.number,
+                        "targetPort": port.name,
+                    }))
+                    .collect::<Vec<_>>(),
+            },
+        }))
+    }
+
+    /// Writes `configmap.yaml`, `deployment.yaml`, `service.yaml` and, when the
+    /// config has placeholders, `secret.yaml.example` to `dir`.
+    pub(super) fn write_manifests(&self, dir: &Path) -> anyhow::Result<()> {
+        write(dir, "configmap.yaml", &to_yaml(&self.config_map())?)?;
+        write(
+            dir,
+            "deployment.yaml",
+            &to_yaml(&self.deployment(self.image, &self.secret_name()))?,
+        )?;
+        if let Some(service) = self.service() {
+            write(dir, "service.yaml", &to_yaml(&service)?)?;
+        }
+        if let Some(example) = self.secret_example()? {
+            write(dir, "secret.yaml.example", &example)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a Helm chart to `dir`: the same manifests with the image and the
+    /// replica count taken from `values.yaml`, and the config as a chart file.
+    pub(super) fn write_chart(&self, dir: &Path) -> anyhow::Result<()> {
+        let (repository, tag) = split_image(self.image);
+        write(
+            dir,
+            "Chart.yaml",
+            &to_yaml(&json!({
+                "apiVersion": "v2",
+                "name": self.name,
+                "description": format!("{} server", self.name),
+                "type": "application",
+                "version": "0.1.0",
+                "appVersion": tag,
+            }))?,
+        )?;
+        let mut values = to_yaml(&json!({
+            "replicaCount": 1,
+            "image": { "repository": repository, "tag": tag },
+        }))?;
+        if !self.variables.is_empty() {
+            let (defaulted, required) = self.split_variables();
+            let list = |names: &[&str]| {
+                if names.is_empty() {
+                    "(none)".to_owned()
+                } else {
+                    names.join(", ")
+                }
+            };
+            _ = write!(
+                values,
+                "# Values of the config's placeholders, stored in the {secret} secret.\n# Required: {}.\n# Optional, the config has defaults for them: {}.\nenv: {{}}\n# Name of an existing secret holding them instead.\nexistingSecret: \"\"\n",
+                list(&required),
+                list(&defaulted),
+                secret = self.secret_name(),
+            );
+        }
+        write(dir, "values.yaml", &values)?;
+        write(dir, &format!("files/{}", self.config_file()), &self.source)?;
+
+        let name = &self.name;
+        let file = self.config_file();
+        write(
+            dir,
+            "templates/configmap.yaml",
+            &format!(
+                "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: {name}-config\n  labels:\n    app.kubernetes.io/name: {name}\ndata:\n  {file}: |-\n{{{{ .Files.Get \"files/{file}\" | indent 4 }}}}\n"
+            ),
+        )?;
+        let secret_name = format!(
+            "{{{{ .Values.existingSecret | default `{}` }}}}",
+            self.secret_name()
+        );
+        let deployment = to_yaml(&self.deployment(
+            "{{ .Values.image.repository }}:{{ .Values.image.tag }}",
+            &secret_name,
+        ))?
+        .replacen("replicas: 1\n", "replicas: {{ .Values.replicaCount }}\n", 1);
+        write(dir, "templates/deployment.yaml", &deployment)?;
+        if let Some(service) = self.service() {
+            write(dir, "templates/service.yaml", &to_yaml(&service)?)?;
+        }
+        if let Some(secret) = self.secret_template() {
+            write(dir, "templates/secret.yaml", &secret)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `rest_host` and `grpc_hub` ports, read from the providers'
+/// `bind_addr`, `listen_addr` or `port` settings. Workspace providers are only
+/// recognized once their local module metadata is merged into `config`, as
+/// [`crate::common::get_config`] does.
+pub(super) fn ports(config: &AppConfig) -> Vec<Port> {
+    [
+        (Capability::RestHost, "http", 8080),
+        (Capability::GrpcHub, "grpc", 50051),
+    ]
+    .into_iter()
+    .filter_map(|(capability, name, default)| {
+        let (module, module_config) = config.providers(&capability).next()?;
+        let number = configured_port(module_config).unwrap_or_else(|| {
+            eprintln!(
+                "note: no port found in modules.{module}.config, assuming {default} for the {name} port"
+            );
+            default
+        });
+        Some(Port { name, number })
+    })
+    .collect()
+}
+
+/// A Kubernetes object name for `name`: lowercase letters, digits and `-`.
+pub(super) fn object_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_owned()
+}
+
+/// The port a module listens on, warning when it only listens on loopback.
+fn configured_port(module: &ModuleConfig) -> Option<u16> {
+    let address = module.listen_address()?;
+    if let Some(host @ ("127.0.0.1" | "localhost" | "[::1]")) = address.host {
+        eprintln!(
+            "warning: {} '{host}:{}' only listens on loopback, the container's port won't be reachable",
+            address.key, address.port
+        );
+    }
+    Some(address.port)
+}
+
+/// `registry/name:tag` as its repository and tag, `latest` when untagged.
+fn split_image(image: &str) -> (&str, &str) {
+    match image.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+        _ => (image, "latest"),
+    }
+}
+
+pub(super) fn write(dir: &Path, file: &str, contents: &str) -> anyhow::Result<()> {
+    let path = dir.join(file);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("can't create directory {}", parent.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("can't write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Port, Workload, ports, split_image};
+    use crate::app_config::AppConfig;
+    use module_parser::{Capability, ConfigModuleMetadata};
+    use serde_json::json;
+
+    fn workload(yaml: &str) -> Workload<'static> {
+        let config: AppConfig = serde_saphyr::from_str(yaml).expect("valid config");
+        Workload::from_config(
+            "Demo_Server",
+            "registry.acme.io/demo:1.2.0",
+            yaml.to_owned(),
+            "yml",
+            ports(&config),
+        )
+        .expect("valid config")
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn manifests_expose_provider_ports_and_read_placeholders_from_a_secret() {
+        let workload = workload(
+            "server:
+  home_dir: ~/.cyberfabric
+database:
+  servers:
+    main:
+      dsn: postgres://app:${DB_PASSWORD}@db:5432/app
+modules:
+  api-gateway:
+    config:
+      bind_addr: 0.0.0.0:8087
+      title: ${TITLE:-Demo}
+",
+        );
+
+        assert_eq!(workload.name, "demo-server");
+        assert_eq!(
+            workload.ports,
+            [Port {
+                name: "http",
+                number: 8087
+            }]
+        );
+
+        let deployment = workload.deployment(workload.image, &workload.secret_name());
+        let container = &deployment["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(container["image"], "registry.acme.io/demo:1.2.0");
+        assert_eq!(
+            container["env"],
+            json!([
+                { "name": "CF_CLI_CONFIG", "value": "/app/config/config.yml" },
+                { "name": "DB_PASSWORD", "valueFrom": { "secretKeyRef": {
+                    "name": "demo-server-env", "key": "DB_PASSWORD", "optional": false } } },
+                { "name": "TITLE", "valueFrom": { "secretKeyRef": {
+                    "name": "demo-server-env", "key": "TITLE", "optional": true } } },
+            ])
+        );
+        assert_eq!(container["readinessProbe"]["tcpSocket"]["port"], "http");
+        assert_eq!(
+            workload.service().expect("a service")["spec"]["ports"],
+            json!([{ "name": "http", "port": 8087, "targetPort": "http" }])
+        );
+        assert_eq!(
+            workload.config_map()["data"]["config.yml"],
+            workload.source.as_str()
+        );
+        let example = workload
+            .secret_example()
+            .expect("secret example")
+            .expect("a secret example");
+        assert!(example.contains("stringData:\n  DB_PASSWORD: \"\"\n"));
+        assert!(example.contains("# Optional, the config has defaults for them: TITLE\n"));
+        assert!(!example.contains("TITLE: "));
+        let template = workload.secret_template().expect("a secret template");
+        assert!(template.contains("(index .Values.env \"DB_PASSWORD\")"));
+        assert!(!template.contains("\"TITLE\""));
+        assert_eq!(
+            split_image("registry.acme.io:5000/demo"),
+            ("registry.acme.io:5000/demo", "latest")
+        );
+    }
+
+    #[test]
+    fn workspace_providers_are_found_by_their_capabilities() {
+        let yaml = "server:
+  home_dir: ~/.cyberfabric
+modules:
+  edge:
+    config:
+      port: 9000
+";
+        let mut config: AppConfig = serde_saphyr::from_str(yaml).expect("valid config");
+        assert!(ports(&config).is_empty());
+
+        // As `get_config` merges it from the workspace module.
+        if let Some(edge) = config.modules.get_mut("edge") {
+            edge.metadata = Some(ConfigModuleMetadata {
+                capabilities: vec![Capability::RestHost],
+                ..ConfigModuleMetadata::default()
+            });
+        }
+        let workload =
+            Workload::from_config("demo", "demo:1.0", yaml.to_owned(), "yml", ports(&config))
+                .expect("valid config");
+
+        assert_eq!(
+            workload.ports,
+            [Port {
+                name: "http",
+                number: 9000
+            }]
+        );
+        assert!(workload.service().is_some());
+    }
+}
@@ -1,6 +1,15 @@
-use crate::common::{self, PathConfigArgs};
+mod compose;
+mod image;
+mod k8s;
+
+pub use image::BuildTool;
+
+use crate::common::{self, EnvArgs, LayeredConfigArgs};
+use crate::settings::ProjectSettings;
 use anyhow::{Context, bail};
 use clap::Args;
+use std::collections::BTreeMap;
+use std::env;
 use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -8,14 +17,42 @@ use std::process::Command;
 use std::str::FromStr;
 
 const DOCKERFILE_CONTENT: &str = include_str!("../../shared/Dockerfile");
+/// The Dockerfile earlier versions wrote, replaced by the current one while unmodified.
+const LEGACY_DOCKERFILE_CONTENT: &str = include_str!("../../shared/Dockerfile.legacy");
 
 #[derive(Args)]
 pub struct DeployArgs {
     #[command(flatten)]
-    path_config: PathConfigArgs,
-    /// Tag to apply to the generated Docker image
-    #[arg(short = 't', long, value_name = "TAG")]
-    tag: Option<String>,
+    path_config: LayeredConfigArgs,
+    #[command(flatten)]
+    env: EnvArgs,
+    /// Tag to apply to the generated Docker image, repeatable; defaults to the project
+    /// settings tag or `cyberfabric:<cli version>`
+    #[arg(short = 't', long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    /// Target platforms, such as `linux/amd64,linux/arm64`; builds with buildx
+    #[arg(long = "platform", value_name = "PLATFORMS", value_delimiter = ',')]
+    platforms: Vec<String>,
+    /// Push the image to its registry once built; builds with buildx
+    #[arg(long, conflicts_with = "output")]
+    push: bool,
+    /// Output instead of the local image store, such as `type=oci,dest=image.tar` with docker
+    #[arg(long, value_name = "SPEC")]
+    output: Option<String>,
+    /// Tool that builds the image; defaults to the project settings builder or the first
+    /// of docker, podman and buildah found in `PATH`
+    #[arg(long, value_enum)]
+    builder: Option<BuildTool>,
+    /// Write a Kubernetes `Deployment`, `Service`, `ConfigMap` and an example `Secret` for the image to this directory
+    #[arg(long, value_name = "DIR")]
+    k8s: Option<PathBuf>,
+    /// Write a Helm chart to the `--k8s` directory instead of plain manifests
+    #[arg(long, requires = "k8s")]
+    helm: bool,
+    /// Write a `docker-compose.yml` running the image with its databases and an
+    /// OpenTelemetry collector, and the config pointed at them, to this directory
+    #[arg(long, value_name = "DIR")]
+    compose: Option<PathBuf>,
     /// Cargo manifest to build instead of generating a server project
     #[arg(short = 'm', long, value_name = "Cargo.toml")]
     manifest: Option<PathBuf>,
@@ -32,25 +69,44 @@ pub struct DeployArgs {
 
 impl DeployArgs {
     pub fn run(&self) -> anyhow::Result<()> {
-        let config_path = self.path_config.resolve_config()?;
-        let (manifest_path, artifact_name) = if let Some(manifest) = &self.manifest {
-            let manifest_path = resolve_manifest(manifest)?;
-            let artifact_name = manifest_package_name(&manifest_path)?;
-            (manifest_path, artifact_name)
-        } else {
-            let project_name = common::resolve_generated_project_name(&config_path, None)?;
-            let dependencies = common::get_config(&config_path)?.create_dependencies()?;
-            common::generate_server_structure(&project_name, &dependencies)?;
-            (
-                common::generated_project_dir(&project_name)?.join("Cargo.toml"),
-                project_name,
-            )
-        };
+        let layers = self.path_config.resolve_layers()?;
+        // Placeholders stay in the image config: resolved secrets must never be
+        // baked into a layer, so only check that they resolve here.
+        if let Some(vars) = self.env.vars()? {
+            layers.check_env(&vars)?;
+            eprintln!(
+                "note: `${{VAR}}` placeholders are kept in the image config; provide the variables to the container at runtime"
+            );
+        }
+        let (manifest_path, artifact_name, config_path, config) =
+            if let Some(manifest) = &self.manifest {
+                let manifest_path = resolve_manifest(manifest)?;
+                let artifact_name = manifest_package_name(&manifest_path)?;
+                let config_path = layers.materialize(&artifact_name, None)?;
+                (manifest_path, artifact_name, config_path, None)
+            } else {
+                let project_name = common::resolve_generated_project_name(layers.last(), None)?;
+                let config_path = layers.materialize(&project_name, None)?;
+                let config = common::get_config(&config_path)?;
+                let dependencies = config.clone().create_dependencies(layers.last())?;
+                common::generate_server_structure(&project_name, &dependencies)?;
+                (
+                    common::generated_project_dir(&project_name)?.join("Cargo.toml"),
+                    project_name,
+                    config_path,
+                    Some(config),
+                )
+            };
 
         let workspace_root = common::workspace_root()?
             .canonicalize()
             .context("can't canonicalize workspace root")?;
         ensure_dockerfile(&workspace_root)?;
+        let settings = ProjectSettings::load(&workspace_root)?;
+        let dockerfile = self
+            .dockerfile
+            .as_ref()
+            .or(settings.deploy.dockerfile.as_ref());
 
         let manifest_arg = path_inside_build_context(&manifest_path, &workspace_root, "manifest")?;
         let config_arg = path_inside_build_context(&config_path, &workspace_root, "config")?;
@@ -59,39 +115,112 @@ impl DeployArgs {
             .and_then(std::ffi::OsStr::to_str)
             .context("config must have a file extension")?;
 
-        let mut command = Command::new("docker");
-        command.arg("build");
-        add_build_arg(&mut command, "BUILDER_MANIFEST", &manifest_arg);
-        add_build_arg(
-            &mut command,
-            "BUILD_MODE",
-            if self.debug { "debug" } else { "release" },
-        );
-        add_build_arg(&mut command, "ARTIFACT_NAME", &artifact_name);
-        add_build_arg(&mut command, "LOCAL_CONFIG_PATH", &config_arg);
-        add_build_arg(&mut command, "CONFIG_EXT", config_ext);
-        for arg in &self.args {
-            command.arg("--build-arg").arg(arg.to_string());
-        }
-        if let Some(tag) = &self.tag {
-            command.arg("--tag").arg(tag);
+        let build_args = [
+            (
+                "BUILDER_MANIFEST",
+                manifest_arg.to_string_lossy().into_owned(),
+            ),
+            (
+                "BUILD_MODE",
+                if self.debug { "debug" } else { "release" }.to_owned(),
+            ),
+            ("ARTIFACT_NAME", artifact_name.clone()),
+            (
+                "LOCAL_CONFIG_PATH",
+                config_arg.to_string_lossy().into_owned(),
+            ),
+            ("CONFIG_EXT", config_ext.to_owned()),
+        ]
+        .into_iter()
+        .chain(source_date_epoch(&workspace_root).map(|epoch| ("SOURCE_DATE_EPOCH", epoch)))
+        .map(|(key, value)| DockerBuildArg {
+            key: key.to_owned(),
+            value,
+        })
+        .chain(merge_build_args(&settings.deploy.args, &self.args))
+        .collect();
+        let tags = if self.tags.is_empty() {
+            vec![
+                settings
+                    .deploy_tag(&artifact_name, || workspace_version(&workspace_root))?
+                    .unwrap_or_else(|| format!("cyberfabric:{}", env!("CARGO_PKG_VERSION"))),
+            ]
         } else {
-            let default_tag = format!("cyberfabric:{}", env!("CARGO_PKG_VERSION"));
-            command.arg("--tag").arg(default_tag);
-        }
-        if let Some(dockerfile) = &self.dockerfile {
-            let canonical_dockerfile = dockerfile
-                .canonicalize()
-                .with_context(|| format!("dockerfile doesn't exists: {}", dockerfile.display()))?;
-            command.arg("--file").arg(&canonical_dockerfile);
-        }
+            self.tags.clone()
+        };
+        let dockerfile = dockerfile
+            .map(|dockerfile| {
+                dockerfile
+                    .canonicalize()
+                    .with_context(|| format!("dockerfile doesn't exists: {}", dockerfile.display()))
+            })
+            .transpose()?;
+        let platforms = if self.platforms.is_empty() {
+            settings.deploy.platforms.clone()
+        } else {
+            self.platforms.clone()
+        };
 
-        command.arg(".");
-        command.current_dir(&workspace_root);
+        let tool = match self.builder.or(settings.deploy.builder) {
+            Some(tool) => tool,
+            None => BuildTool::detect()?,
+        };
+        let image = tags[0].clone();
+        let build = image::ImageBuild {
+            context: workspace_root,
+            dockerfile,
+            build_args,
+            tags,
+            platforms,
+            push: self.push,
+            output: self.output.clone(),
+        };
+        for mut command in build.commands(tool)? {
+            let step = command
+                .get_args()
+                .next()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let status = command
+                .status()
+                .with_context(|| format!("failed to run {tool} {step}"))?;
+            if !status.success() {
+                bail!("{tool} {step} exited with {status}");
+            }
+        }
 
-        let status = command.status().context("failed to run docker build")?;
-        if !status.success() {
-            bail!("docker build exited with {status}");
+        if self.k8s.is_none() && self.compose.is_none() {
+            return Ok(());
+        }
+        // With the local module metadata merged in, workspace modules
+        // providing `rest_host` or `grpc_hub` are found as well.
+        let config = match config {
+            Some(config) => config,
+            None => common::get_config(&config_path)?,
+        };
+        let ports = k8s::ports(&config);
+        if let Some(dir) = &self.k8s {
+            let workload = k8s::Workload::new(
+                &artifact_name,
+                &image,
+                &config_path,
+                config_ext,
+                ports.clone(),
+            )?;
+            if self.helm {
+                workload.write_chart(dir)?;
+            } else {
+                workload.write_manifests(dir)?;
+            }
+            eprintln!("wrote Kubernetes manifests to {}", dir.display());
+        }
+        if let Some(dir) = &self.compose {
+            compose::Stack::new(&artifact_name, &image, &config_path, config_ext, ports)?
+                .write(dir)?;
+            eprintln!(
+                "wrote a compose stack to {}; start it with `docker compose up`",
+                dir.display()
+            );
         }
 
         Ok(())
@@ -101,13 +230,74 @@ impl DeployArgs {
 fn ensure_dockerfile(workspace_root: &Path) -> anyhow::Result<()> {
     let dockerfile_path = workspace_root.join("Dockerfile");
     if dockerfile_path.exists() {
-        return Ok(());
+        let current = fs::read_to_string(&dockerfile_path)
+            .with_context(|| format!("failed to read {}", dockerfile_path.display()))?;
+        if current != LEGACY_DOCKERFILE_CONTENT {
+            return Ok(());
+        }
+        eprintln!(
+            "note: updating the unmodified {} to the dependency-caching Dockerfile",
+            dockerfile_path.display()
+        );
     }
 
     fs::write(&dockerfile_path, DOCKERFILE_CONTENT)
         .with_context(|| format!("failed to write {}", dockerfile_path.display()))
 }
 
+/// Project ARGs first, each replaced by a command line `--args` of the same key.
+fn merge_build_args(
+    defaults: &BTreeMap<String, String>,
+    overrides: &[DockerBuildArg],
+) -> Vec<DockerBuildArg> {
+    defaults
+        .iter()
+        .filter(|(key, _)| !overrides.iter().any(|arg| &arg.key == *key))
+        .map(|(key, value)| DockerBuildArg {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .chain(overrides.iter().cloned())
+        .collect()
+}
+
+/// `SOURCE_DATE_EPOCH` from the environment, else the time of the workspace's
+/// last commit, so that rebuilding the same sources gives the same image.
+fn source_date_epoch(workspace_root: &Path) -> Option<String> {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["log", "-1", "--format=%ct"])
+                .current_dir(workspace_root)
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        })
+        .filter(|epoch| !epoch.is_empty())
+}
+
+/// `[workspace.package].version` or `[package].version` of the workspace manifest.
+fn workspace_version(workspace_root: &Path) -> anyhow::Result<String> {
+    let manifest_path = workspace_root.join("Cargo.toml");
+    let manifest = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("failed to read manifest {}", manifest_path.display()))?;
+    let manifest: toml::Value = toml::from_str(&manifest)
+        .with_context(|| format!("failed to parse manifest {}", manifest_path.display()))?;
+
+    manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("package"))
+        .or_else(|| manifest.get("package"))
+        .and_then(|package| package.get("version"))
+        .and_then(toml::Value::as_str)
+        .map(ToOwned::to_owned)
+        .context("the deploy tag uses {version} but the workspace manifest has no package version")
+}
+
 fn resolve_manifest(manifest: &Path) -> anyhow::Result<PathBuf> {
     if manifest.file_name().and_then(std::ffi::OsStr::to_str) != Some("Cargo.toml") {
         bail!("manifest must point to a Cargo.toml file");
@@ -151,15 +341,6 @@ fn path_inside_build_context(
         })
 }
 
-fn add_build_arg<T>(command: &mut Command, key: &str, value: T)
-where
-    T: AsRef<std::ffi::OsStr>,
-{
-    command
-        .arg("--build-arg")
-        .arg(format!("{key}={}", value.as_ref().to_string_lossy()));
-}
-
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct DockerBuildArg {
     key: String,
@@ -192,8 +373,12 @@ impl fmt::Display for DockerBuildArg {
 
 #[cfg(test)]
 mod tests {
-    use super::{DockerBuildArg, manifest_package_name, resolve_manifest};
+    use super::{
+        DOCKERFILE_CONTENT, DockerBuildArg, LEGACY_DOCKERFILE_CONTENT, ensure_dockerfile,
+        manifest_package_name, merge_build_args, resolve_manifest, workspace_version,
+    };
     use module_parser::test_utils::TempDirExt;
+    use std::collections::BTreeMap;
     use std::path::Path;
     use tempfile::TempDir;
 
@@ -209,6 +394,41 @@ mod tests {
         assert!("=value".parse::<DockerBuildArg>().is_err());
     }
 
+    #[test]
+    fn ensure_dockerfile_upgrades_only_the_unmodified_legacy_dockerfile() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dockerfile = temp_dir.path().join("Dockerfile");
+
+        ensure_dockerfile(temp_dir.path())?;
+        assert_eq!(std::fs::read_to_string(&dockerfile)?, DOCKERFILE_CONTENT);
+
+        temp_dir.write("Dockerfile", LEGACY_DOCKERFILE_CONTENT);
+        ensure_dockerfile(temp_dir.path())?;
+        assert_eq!(std::fs::read_to_string(&dockerfile)?, DOCKERFILE_CONTENT);
+
+        let custom = format!("{LEGACY_DOCKERFILE_CONTENT}\nEXPOSE 8080\n");
+        temp_dir.write("Dockerfile", &custom);
+        ensure_dockerfile(temp_dir.path())?;
+        assert_eq!(std::fs::read_to_string(&dockerfile)?, custom);
+        Ok(())
+    }
+
+    #[test]
+    fn source_date_epoch_does_not_invalidate_the_dependency_layer() {
+        let position = |needle: &str| {
+            DOCKERFILE_CONTENT
+                .find(needle)
+                .unwrap_or_else(|| panic!("Dockerfile should contain {needle}"))
+        };
+
+        assert_eq!(
+            DOCKERFILE_CONTENT.matches("ARG SOURCE_DATE_EPOCH").count(),
+            1
+        );
+        assert!(position("cargo chef cook") < position("ARG SOURCE_DATE_EPOCH"));
+        assert!(position("ARG SOURCE_DATE_EPOCH") < position("cargo build"));
+    }
+
     #[test]
     fn resolve_manifest_requires_cargo_toml_filename() -> anyhow::Result<()> {
         let temp_dir = TempDir::new()?;
@@ -238,4 +458,34 @@ edition = "2024"
         assert_eq!(name, "demo-server");
         Ok(())
     }
+
+    #[test]
+    fn command_line_build_args_override_project_defaults() {
+        let defaults = BTreeMap::from([
+            ("BUILDER_FLAGS".to_owned(), "--locked".to_owned()),
+            ("RUST_VERSION".to_owned(), "1.92".to_owned()),
+        ]);
+        let overrides = vec!["RUST_VERSION=1.93".parse::<DockerBuildArg>().expect("arg")];
+
+        let merged: Vec<_> = merge_build_args(&defaults, &overrides)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(merged, vec!["BUILDER_FLAGS=--locked", "RUST_VERSION=1.93"]);
+    }
+
+    #[test]
+    fn workspace_version_prefers_workspace_package() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        temp_dir.write(
+            "Cargo.toml",
+            "[workspace.package]\nversion = \"2.1.0\"\n\n[package]\nversion = \"0.1.0\"\n",
+        );
+
+        assert_eq!(workspace_version(temp_dir.path())?, "2.1.0");
+        temp_dir.write("Cargo.toml", "[workspace]\nmembers = []\n");
+        assert!(workspace_version(temp_dir.path()).is_err());
+        Ok(())
+    }
 }
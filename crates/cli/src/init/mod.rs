@@ -1,3 +1,7 @@
+use crate::common::workspace_root;
+use crate::settings::{
+    DEFAULT_INIT_SUBFOLDER, DEFAULT_TEMPLATE_BRANCH, DEFAULT_TEMPLATE_GIT, ProjectSettings,
+};
 use anyhow::{Context, bail};
 use cargo_generate::{GenerateArgs, TemplatePath, generate};
 use clap::Args;
@@ -23,19 +27,18 @@ pub struct InitArgs {
     #[arg(short = 'v', long)]
     verbose: bool,
     /// Path to a local template (instead of git)
-    #[arg(long, conflicts_with_all = ["git", "subfolder", "branch"])]
+    #[arg(long, conflicts_with_all = ["git", "branch"])]
     local_path: Option<String>,
-    /// url to the git repo
-    #[arg(
-        long,
-        default_value = "https://github.com/cyberfabric/cf-template-rust"
-    )]
+    /// url to the git repo, defaults to the project settings or
+    /// cyberfabric/cf-template-rust on GitHub
+    #[arg(long)]
     git: Option<String>,
-    /// Subfolder relative to the git repo
-    #[arg(long, default_value = "Init")]
+    /// Subfolder relative to the git repo or the local template, defaults to
+    /// the project settings or Init
+    #[arg(long)]
     subfolder: Option<String>,
-    /// Branch of the git repo
-    #[arg(long, default_value = "main")]
+    /// Branch of the git repo, defaults to the project settings or main
+    #[arg(long)]
     branch: Option<String>,
     #[arg(long)]
     r#override: bool,
@@ -58,14 +61,32 @@ impl InitArgs {
                 .to_str()
                 .context("name is strange")?,
         };
+        // Defaults come from the settings of the workspace `init` runs in.
+        let defaults = ProjectSettings::load(&workspace_root()?)?.init;
         let (git, branch) = if self.local_path.is_some() {
             (None, None)
         } else {
-            (self.git.clone(), self.branch.clone())
+            (
+                self.git
+                    .clone()
+                    .or(defaults.git)
+                    .or_else(|| Some(DEFAULT_TEMPLATE_GIT.to_owned())),
+                self.branch
+                    .clone()
+                    .or(defaults.branch)
+                    .or_else(|| Some(DEFAULT_TEMPLATE_BRANCH.to_owned())),
+            )
         };
+        // Local templates keep the same layout, so the subfolder applies to
+        // them as well.
+        let subfolder = self
+            .subfolder
+            .clone()
+            .or(defaults.subfolder)
+            .unwrap_or_else(|| DEFAULT_INIT_SUBFOLDER.to_owned());
         generate(GenerateArgs {
             template_path: TemplatePath {
-                auto_path: self.subfolder.clone(),
+                auto_path: Some(subfolder),
                 git,
                 path: self.local_path.clone(),
                 subfolder: None, // This is only used when git, path and favorite are not specified
@@ -114,3 +135,45 @@ impl InitArgs {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::InitArgs;
+    use crate::settings::DEFAULT_INIT_SUBFOLDER;
+    use std::fs;
+
+    #[test]
+    fn local_templates_are_generated_from_their_init_subfolder() {
+        let temp = tempfile::TempDir::new().expect("temp dir");
+        let template = temp.path().join("template");
+        let init = template.join(DEFAULT_INIT_SUBFOLDER);
+        fs::create_dir_all(init.join("src")).expect("template dir");
+        fs::write(
+            init.join("Cargo.toml"),
+            "[package]\nname = \"{{project-name}}\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+        )
+        .expect("manifest");
+        fs::write(init.join("src/main.rs"), "fn main() {}\n").expect("main");
+        fs::write(template.join("README.md"), "template repository\n").expect("readme");
+
+        let destination = temp.path().join("demo-app");
+        InitArgs {
+            path: destination.clone(),
+            name: None,
+            verbose: false,
+            local_path: Some(template.display().to_string()),
+            git: None,
+            subfolder: None,
+            branch: None,
+            r#override: false,
+        }
+        .run()
+        .expect("init");
+
+        let manifest =
+            fs::read_to_string(destination.join("Cargo.toml")).expect("generated manifest");
+        assert!(manifest.contains("name = \"demo-app\""));
+        assert!(destination.join("src/main.rs").is_file());
+        assert!(!destination.join("README.md").exists());
+    }
+}
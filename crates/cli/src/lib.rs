@@ -8,6 +8,7 @@ mod init;
 mod lint;
 mod r#mod;
 mod run;
+mod settings;
 mod test;
 mod tools;
 
@@ -24,7 +25,7 @@ pub struct Cli {
 pub enum Commands {
     /// Initialize a new project in a non-existing folder
     Init(init::InitArgs),
-    /// Add new modules to an existing project
+    /// Add, remove and list modules of an existing project
     Mod(r#mod::ModArgs),
     /// Utility to modify a provided configuration file
     Config(Box<config::ConfigArgs>),
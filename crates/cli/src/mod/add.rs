@@ -1,6 +1,11 @@
+use super::templates;
+use crate::config::validate_name;
+use crate::settings::{
+    DEFAULT_MODULES_SUBFOLDER, DEFAULT_TEMPLATE_BRANCH, ProjectSettings, TemplateSource,
+};
 use anyhow::{Context, bail};
 use cargo_generate::{GenerateArgs, TemplatePath, generate};
-use clap::{Args, ValueEnum};
+use clap::Args;
 use module_parser::{CargoTomlDependencies, CargoTomlDependency};
 use semver::{Comparator, Op, Version, VersionReq};
 use std::fs;
@@ -8,9 +13,8 @@ use std::path::{Path, PathBuf};
 
 #[derive(Args)]
 pub struct AddArgs {
-    /// Module template and module name to generate
-    #[arg(value_enum)]
-    name: ModuleTemplateName,
+    /// Module template to generate; also used as the module name
+    name: String,
     /// Path to the workspace root (defaults to current directory)
     #[arg(short = 'p', long, default_value = ".")]
     path: PathBuf,
@@ -18,40 +22,22 @@ pub struct AddArgs {
     #[arg(short = 'v', long)]
     verbose: bool,
     /// Path to a local template (instead of git)
-    #[arg(long, conflicts_with_all = ["git", "branch"])]
+    #[arg(long, conflicts_with_all = ["git", "branch", "source"])]
     local_path: Option<String>,
-    /// URL to the git repo
-    #[arg(
-        long,
-        default_value = "https://github.com/cyberfabric/cf-template-rust"
-    )]
+    /// URL to the git repo, defaults to the project settings or
+    /// cyberfabric/cf-template-rust on GitHub
+    #[arg(long, conflicts_with = "source")]
     git: Option<String>,
-    /// Subfolder relative to the git repo
-    #[arg(long, default_value = "Modules")]
-    subfolder: String,
-    /// Branch of the git repo
-    #[arg(long, default_value = "main")]
+    /// Subfolder relative to the template root, defaults to the project settings or Modules
+    #[arg(long)]
+    subfolder: Option<String>,
+    /// Branch of the git repo, defaults to the project settings or main; applies to
+    /// the project template sources too
+    #[arg(long)]
     branch: Option<String>,
-}
-
-#[derive(Clone, Debug, ValueEnum)]
-enum ModuleTemplateName {
-    #[value(name = "background-worker")]
-    BackgroundWorker,
-    #[value(name = "api-db-handler")]
-    ApiDbHandler,
-    #[value(name = "rest-gateway")]
-    RestGateway,
-}
-
-impl ModuleTemplateName {
-    const fn as_str(&self) -> &'static str {
-        match self {
-            Self::BackgroundWorker => "background-worker",
-            Self::ApiDbHandler => "api-db-handler",
-            Self::RestGateway => "rest-gateway",
-        }
-    }
+    /// Template source from the project settings to take the template from
+    #[arg(long)]
+    source: Option<String>,
 }
 
 struct StagedModuleWrite {
@@ -77,28 +63,66 @@ impl AddArgs {
         Ok(())
     }
 
+    /// Explicit `--git`/`--local-path` flags win, then `--source`. Otherwise
+    /// the project template sources are searched in order; without project
+    /// sources the default repository is used directly. `--branch` replaces
+    /// the branch of every source, so the search looks at that branch too.
+    fn resolve_template_source(&self) -> anyhow::Result<TemplateSource> {
+        let settings = ProjectSettings::load(&self.path)?;
+        let mut source = if self.local_path.is_some() || self.git.is_some() {
+            TemplateSource {
+                name: "command line".to_owned(),
+                git: self.git.clone(),
+                branch: self.git.as_ref().map(|_| {
+                    self.branch
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_TEMPLATE_BRANCH.to_owned())
+                }),
+                path: self.local_path.as_ref().map(PathBuf::from),
+                subfolder: settings
+                    .r#mod
+                    .subfolder
+                    .unwrap_or_else(|| DEFAULT_MODULES_SUBFOLDER.to_owned()),
+            }
+        } else {
+            let mut sources = settings.module_template_sources();
+            if let Some(branch) = &self.branch {
+                for source in &mut sources {
+                    source.branch = Some(branch.clone());
+                }
+            }
+            if let Some(name) = &self.source {
+                templates::find_source(&sources, name)?.clone()
+            } else if let [source] = sources.as_slice() {
+                source.clone()
+            } else {
+                templates::find_source_with_template(&sources, &self.name)?.clone()
+            }
+        };
+        if let Some(subfolder) = &self.subfolder {
+            source.subfolder.clone_from(subfolder);
+        }
+        Ok(source)
+    }
+
     fn generate_module(&self) -> anyhow::Result<Vec<String>> {
         let module_name = self.name.as_str();
+        validate_name(module_name, "template")?;
         let modules_path = self.path.join("modules");
         let module_path = modules_path.join(module_name);
         if module_path.exists() {
             bail!("module {module_name} already exists");
         }
 
-        let (git, branch) = if self.local_path.is_some() {
-            (None, None)
-        } else {
-            (self.git.clone(), self.branch.clone())
-        };
-
-        let auto_path = format!("{}/{}", self.subfolder, module_name);
+        let source = self.resolve_template_source()?;
+        let auto_path = format!("{}/{}", source.subfolder, module_name);
 
         generate(GenerateArgs {
             template_path: TemplatePath {
                 auto_path: Some(auto_path),
-                git,
-                path: self.local_path.clone(),
-                branch,
+                git: source.git,
+                path: source.path.map(|path| path.display().to_string()),
+                branch: source.branch,
                 ..TemplatePath::default()
             },
             destination: Some(modules_path),
@@ -211,7 +235,7 @@ fn update_workspace_cargo_toml(
     save_toml_document(&cargo_toml_path, &workspace_doc)
 }
 
-fn get_cargo_toml(path: &Path) -> anyhow::Result<toml_edit::DocumentMut> {
+pub(super) fn get_cargo_toml(path: &Path) -> anyhow::Result<toml_edit::DocumentMut> {
     let cargo_toml_path = path.join("Cargo.toml");
     fs::read_to_string(&cargo_toml_path)
         .with_context(|| format!("can't read {}", path.display()))?
@@ -446,7 +470,7 @@ fn get_dep_value<'a>(dep: &'a toml_edit::Item, key: &str) -> Option<&'a toml_edi
         .or_else(|| dep.as_inline_table().and_then(|t| t.get(key)))
 }
 
-fn get_dep_str_field(dep: &toml_edit::Item, key: &str) -> Option<String> {
+pub(super) fn get_dep_str_field(dep: &toml_edit::Item, key: &str) -> Option<String> {
     if key == "version"
         && let Some(s) = dep.as_str()
     {
@@ -457,7 +481,7 @@ fn get_dep_str_field(dep: &toml_edit::Item, key: &str) -> Option<String> {
         .map(ToOwned::to_owned)
 }
 
-fn get_dep_bool_field(dep: &toml_edit::Item, key: &str) -> Option<bool> {
+pub(super) fn get_dep_bool_field(dep: &toml_edit::Item, key: &str) -> Option<bool> {
     get_dep_value(dep, key).and_then(toml_edit::Value::as_bool)
 }
 
@@ -525,7 +549,7 @@ fn comparator_floor(comparator: &Comparator) -> Option<Version> {
     }
 }
 
-fn save_toml_document(path: &Path, doc: &toml_edit::DocumentMut) -> anyhow::Result<()> {
+pub(super) fn save_toml_document(path: &Path, doc: &toml_edit::DocumentMut) -> anyhow::Result<()> {
     let mut serialized = doc.to_string();
     if !serialized.ends_with('\n') {
         serialized.push('\n');
@@ -609,12 +633,82 @@ fn remove_dep_key(dep: &mut toml_edit::Item, key: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::{
-        add_dependencies_to_workspace, ensure_workspace_lints_inheritance, get_dependencies,
-        normalize_workspace_package_name, rewrite_dependencies_to_workspace_inheritance,
-        should_replace_with_newer_semver,
+        AddArgs, add_dependencies_to_workspace, ensure_workspace_lints_inheritance,
+        get_dependencies, normalize_workspace_package_name,
+        rewrite_dependencies_to_workspace_inheritance, should_replace_with_newer_semver,
     };
+    use clap::Parser;
+    use module_parser::test_utils::TempDirExt;
     use module_parser::{CargoTomlDependencies, CargoTomlDependency};
     use std::collections::BTreeSet;
+    use tempfile::TempDir;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        add: AddArgs,
+    }
+
+    #[test]
+    fn accepts_custom_template_names() {
+        let cli = TestCli::try_parse_from(["cyberfabric", "kafka-consumer", "--source", "acme"])
+            .expect("custom template should parse");
+        assert_eq!(cli.add.name, "kafka-consumer");
+        assert_eq!(cli.add.source.as_deref(), Some("acme"));
+
+        assert!(
+            TestCli::try_parse_from([
+                "cyberfabric",
+                "kafka-consumer",
+                "--source",
+                "acme",
+                "--git",
+                "x"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn branch_applies_to_the_selected_template_source() {
+        let temp_dir = TempDir::new().expect("temp dir");
+        temp_dir.write(
+            "Cargo.toml",
+            r#"[workspace]
+members = []
+
+[[workspace.metadata.cyberfabric.template-sources]]
+name = "acme"
+path = "acme"
+
+[[workspace.metadata.cyberfabric.template-sources]]
+name = "extra"
+path = "extra"
+"#,
+        );
+        temp_dir.write("acme/Modules/background-worker/Cargo.toml", "");
+        temp_dir.write("extra/Modules/kafka-consumer/Cargo.toml", "");
+        let workspace = temp_dir.path().to_string_lossy().into_owned();
+        let resolve = |args: &[&str]| {
+            let cli = TestCli::try_parse_from(
+                ["cyberfabric", "-p", workspace.as_str()]
+                    .into_iter()
+                    .chain(args.iter().copied()),
+            )
+            .expect("args should parse");
+            let source = cli.add.resolve_template_source().expect("template source");
+            (source.name, source.branch)
+        };
+
+        assert_eq!(
+            resolve(&["kafka-consumer", "--branch", "next"]),
+            ("extra".to_owned(), Some("next".to_owned()))
+        );
+        assert_eq!(
+            resolve(&["kafka-consumer", "--source", "acme", "--branch", "next"]),
+            ("acme".to_owned(), Some("next".to_owned()))
+        );
+    }
 
     #[test]
     fn replaces_workspace_dep_version_with_newer_semver() {
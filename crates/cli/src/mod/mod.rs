@@ -1,6 +1,8 @@
 use clap::{Args, Subcommand};
 
 mod add;
+mod remove;
+mod templates;
 
 #[derive(Args)]
 pub struct ModArgs {
@@ -17,12 +19,19 @@ impl ModArgs {
 #[derive(Subcommand)]
 pub enum ModCommand {
     Add(add::AddArgs),
+    /// Remove a module from the workspace and, optionally, from config files
+    #[command(alias = "rm")]
+    Remove(remove::RemoveArgs),
+    /// Inspect the available module templates
+    Templates(templates::TemplatesArgs),
 }
 
 impl ModCommand {
     pub fn run(&self) -> anyhow::Result<()> {
         match self {
             Self::Add(args) => args.run(),
+            Self::Remove(args) => args.run(),
+            Self::Templates(args) => args.run(),
         }
     }
 }
@@ -0,0 +1,447 @@
+use super::add::{get_cargo_toml, get_dep_bool_field, get_dep_str_field, save_toml_document};
+use crate::common::{parse_and_chdir, workspace_root};
+use crate::config::{edit_config, validate_name, write_config};
+use anyhow::{Context, bail};
+use clap::Args;
+use module_parser::{ConfigModule, get_module_name_from_crate};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+#[derive(Args)]
+pub struct RemoveArgs {
+    /// Name of the module directory under `modules/`
+    name: String,
+    /// Path to the workspace root (defaults to current directory)
+    #[arg(short = 'p', long, value_parser = parse_and_chdir)]
+    path: Option<PathBuf>,
+    /// Config file to drop the module entry from (repeatable)
+    #[arg(short = 'c', long = "config")]
+    configs: Vec<PathBuf>,
+    /// Remove the module even if other modules still list it in their `deps`
+    #[arg(long)]
+    force: bool,
+}
+
+impl RemoveArgs {
+    pub fn run(&self) -> anyhow::Result<()> {
+        validate_name(&self.name, "module")?;
+        let root = workspace_root()?;
+        let module_rel = format!("modules/{}", self.name);
+        let module_dir = root.join(&module_rel);
+        if !module_dir.is_dir() {
+            bail!("module directory {} does not exist", module_dir.display());
+        }
+
+        let local_modules = get_module_name_from_crate()?;
+        let removed_modules = modules_in_dir(&local_modules, &module_dir, &self.name)?;
+        self.check_dependents(&local_modules, &removed_modules)?;
+
+        let mut workspace_doc = get_cargo_toml(&root)?;
+        let removed_members = remove_workspace_members(&mut workspace_doc, &module_rel)?;
+
+        let mut candidates = BTreeSet::new();
+        for crate_dir in removed_members
+            .iter()
+            .map(|member| root.join(member))
+            .chain(std::iter::once(module_dir.clone()))
+        {
+            if crate_dir.join("Cargo.toml").is_file() {
+                candidates.extend(workspace_deps_used(&get_cargo_toml(&crate_dir)?));
+            }
+        }
+        let still_used = remaining_workspace_deps(&root, &workspace_doc, &module_dir)?;
+        let pruned = prune_workspace_dependencies(
+            &mut workspace_doc,
+            &module_rel,
+            &candidates,
+            still_used.as_ref(),
+        );
+
+        let mut staged_configs = Vec::new();
+        for config_path in &self.configs {
+            let (config, dropped) = edit_config(config_path, |config| {
+                Ok(removed_modules
+                    .iter()
+                    .filter(|module| config.modules.remove(module.as_str()).is_some())
+                    .cloned()
+                    .collect::<Vec<_>>())
+            })?;
+            if dropped.is_empty() {
+                println!(
+                    "{}: no entry for module '{}', left unchanged",
+                    config_path.display(),
+                    self.name
+                );
+            } else {
+                staged_configs.push((config_path, config, dropped));
+            }
+        }
+
+        save_toml_document(&root.join("Cargo.toml"), &workspace_doc)?;
+        for (config_path, config, dropped) in &staged_configs {
+            write_config(config_path, config)?;
+            println!(
+                "{}: removed modules.{}",
+                config_path.display(),
+                dropped.join(", modules.")
+            );
+        }
+        fs::remove_dir_all(&module_dir)
+            .with_context(|| format!("can't delete {}", module_dir.display()))?;
+
+        println!("Module '{}' removed", self.name);
+        if !removed_members.is_empty() {
+            println!("  workspace members: {}", removed_members.join(", "));
+        }
+        if !pruned.is_empty() {
+            println!("  workspace dependencies: {}", pruned.join(", "));
+        }
+        Ok(())
+    }
+
+    fn check_dependents(
+        &self,
+        local_modules: &HashMap<String, ConfigModule>,
+        removed_modules: &BTreeSet<String>,
+    ) -> anyhow::Result<()> {
+        let dependents = dependents(local_modules, removed_modules);
+        if dependents.is_empty() {
+            return Ok(());
+        }
+        let listed = dependents
+            .iter()
+            .map(|(module, dep)| format!("  - module '{module}' depends on '{dep}'"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.force {
+            eprintln!("warning: removing a module other modules still depend on:\n{listed}");
+            return Ok(());
+        }
+        bail!(
+            "module '{}' is still a dependency of other modules:\n{listed}\nRemove those deps first or pass --force",
+            self.name
+        );
+    }
+}
+
+/// Returns the modkit module names defined by crates inside `module_dir`,
+/// falling back to the directory name when none are discovered.
+fn modules_in_dir(
+    local_modules: &HashMap<String, ConfigModule>,
+    module_dir: &Path,
+    dir_name: &str,
+) -> anyhow::Result<BTreeSet<String>> {
+    let module_dir = module_dir
+        .canonicalize()
+        .with_context(|| format!("can't canonicalize {}", module_dir.display()))?;
+    let mut names: BTreeSet<_> = local_modules
+        .iter()
+        .filter(|(_, module)| {
+            module.metadata.path.as_deref().is_some_and(|path| {
+                let path = Path::new(path);
+                path.canonicalize()
+                    .unwrap_or_else(|_| path.to_path_buf())
+                    .starts_with(&module_dir)
+            })
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    if names.is_empty() {
+        names.insert(dir_name.to_owned());
+    }
+    Ok(names)
+}
+
+/// `(dependent, dependency)` pairs of remaining modules that still list one of
+/// the removed modules in their `deps`.
+fn dependents(
+    local_modules: &HashMap<String, ConfigModule>,
+    removed_modules: &BTreeSet<String>,
+) -> Vec<(String, String)> {
+    let mut dependents: Vec<_> = local_modules
+        .iter()
+        .filter(|(name, _)| !removed_modules.contains(name.as_str()))
+        .flat_map(|(name, module)| {
+            module
+                .metadata
+                .deps
+                .iter()
+                .filter(|dep| removed_modules.contains(dep.as_str()))
+                .map(|dep| (name.clone(), dep.clone()))
+        })
+        .collect();
+    dependents.sort();
+    dependents
+}
+
+fn normalize_member(member: &str) -> &str {
+    member.trim_start_matches("./").trim_end_matches('/')
+}
+
+fn is_inside(member: &str, module_rel: &str) -> bool {
+    let member = normalize_member(member);
+    member == module_rel
+        || member
+            .strip_prefix(module_rel)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Drops `workspace.members` (and `default-members`) entries that point at the
+/// module or its nested crates, and `default-members` itself once it is empty.
+/// Returns the removed members.
+fn remove_workspace_members(
+    doc: &mut toml_edit::DocumentMut,
+    module_rel: &str,
+) -> anyhow::Result<Vec<String>> {
+    let mut removed = Vec::new();
+    for key in ["members", "default-members"] {
+        let Some(item) = doc
+            .get_mut("workspace")
+            .and_then(toml_edit::Item::as_table_like_mut)
+            .and_then(|workspace| workspace.get_mut(key))
+        else {
+            continue;
+        };
+        let members = item
+            .as_array_mut()
+            .with_context(|| format!("workspace.{key} is not an array"))?;
+        // Keep the array layout when its first element goes away.
+        let first_prefix = members
+            .get(0)
+            .and_then(|member| member.decor().prefix().cloned());
+        members.retain(|member| {
+            let Some(member) = member.as_str() else {
+                return true;
+            };
+            if !is_inside(member, module_rel) {
+                return true;
+            }
+            if key == "members" {
+                removed.push(member.to_owned());
+            }
+            false
+        });
+        if let (Some(first), Some(prefix)) = (members.get_mut(0), first_prefix) {
+            first.decor_mut().set_prefix(prefix);
+        }
+        // Without entries `default-members` would build nothing, while
+        // without the key Cargo builds every member.
+        if key == "default-members"
+            && members.is_empty()
+            && let Some(workspace) = doc
+                .get_mut("workspace")
+                .and_then(toml_edit::Item::as_table_like_mut)
+        {
+            workspace.remove(key);
+        }
+    }
+    Ok(removed)
+}
+
+/// Names of the dependencies a crate inherits with `workspace = true`.
+fn workspace_deps_used(doc: &toml_edit::DocumentMut) -> BTreeSet<String> {
+    let targets = doc
+        .get("target")
+        .and_then(toml_edit::Item::as_table)
+        .into_iter()
+        .flat_map(toml_edit::Table::iter)
+        .map(|(_, target)| target);
+
+    std::iter::once(doc.as_item())
+        .chain(targets)
+        .flat_map(|scope| {
+            DEPENDENCY_TABLES
+                .iter()
+                .filter_map(|key| scope.get(key).and_then(toml_edit::Item::as_table))
+        })
+        .flat_map(toml_edit::Table::iter)
+        .filter(|(_, dep)| get_dep_bool_field(dep, "workspace") == Some(true))
+        .map(|(name, _)| name.to_owned())
+        .collect()
+}
+
+/// Collects the workspace dependencies still inherited by the root package
+/// and the remaining members. Returns `None` when a member glob can't be
+/// expanded, in which case shared dependencies are kept.
+fn remaining_workspace_deps(
+    root: &Path,
+    workspace_doc: &toml_edit::DocumentMut,
+    module_dir: &Path,
+) -> anyhow::Result<Option<BTreeSet<String>>> {
+    let mut used = workspace_deps_used(workspace_doc);
+    let members = workspace_doc["workspace"]["members"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(toml_edit::Value::as_str);
+
+    for member in members {
+        let crate_dirs = if let Some(parent) = member.strip_suffix("/*") {
+            if parent.contains(['*', '?', '[']) {
+                eprintln!(
+                    "warning: can't expand workspace member '{member}', keeping shared workspace dependencies"
+                );
+                return Ok(None);
+            }
+            let parent = root.join(parent);
+            let Ok(entries) = fs::read_dir(&parent) else {
+                continue;
+            };
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| !path.starts_with(module_dir))
+                .collect()
+        } else if member.contains(['*', '?', '[']) {
+            eprintln!(
+                "warning: can't expand workspace member '{member}', keeping shared workspace dependencies"
+            );
+            return Ok(None);
+        } else {
+            vec![root.join(member)]
+        };
+
+        for crate_dir in crate_dirs {
+            if crate_dir.join("Cargo.toml").is_file() {
+                used.extend(workspace_deps_used(&get_cargo_toml(&crate_dir)?));
+            }
+        }
+    }
+    Ok(Some(used))
+}
+
+/// Removes `[workspace.dependencies]` entries that point inside the module, and
+/// the ones only the module inherited when `still_used` is known.
+fn prune_workspace_dependencies(
+    doc: &mut toml_edit::DocumentMut,
+    module_rel: &str,
+    candidates: &BTreeSet<String>,
+    still_used: Option<&BTreeSet<String>>,
+) -> Vec<String> {
+    let Some(workspace_deps) = doc
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(toml_edit::Item::as_table_mut)
+    else {
+        return Vec::new();
+    };
+
+    let pruned: Vec<String> = workspace_deps
+        .iter()
+        .filter(|(name, dep)| {
+            let in_module =
+                get_dep_str_field(dep, "path").is_some_and(|path| is_inside(&path, module_rel));
+            let unused =
+                candidates.contains(*name) && still_used.is_some_and(|used| !used.contains(*name));
+            in_module || unused
+        })
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    for name in &pruned {
+        workspace_deps.remove(name);
+    }
+    pruned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        dependents, prune_workspace_dependencies, remove_workspace_members, workspace_deps_used,
+    };
+    use module_parser::{ConfigModule, ConfigModuleMetadata};
+    use std::collections::{BTreeSet, HashMap};
+
+    const WORKSPACE: &str = r#"[workspace]
+# keep this comment
+members = ["modules/demo", "modules/demo/sdk", "./modules/other/"]
+default-members = ["modules/demo"]
+
+[workspace.dependencies]
+demo-sdk = { path = "modules/demo/sdk" }
+other = { path = "modules/other" }
+rand = "0.9"
+tokio = { version = "1" }
+"#;
+
+    #[test]
+    fn removes_members_and_module_dependencies() {
+        let mut doc = WORKSPACE.parse::<toml_edit::DocumentMut>().expect("toml");
+
+        let removed = remove_workspace_members(&mut doc, "modules/demo").expect("members");
+        let candidates = BTreeSet::from(["rand".to_owned(), "tokio".to_owned()]);
+        let still_used = BTreeSet::from(["tokio".to_owned()]);
+        let pruned =
+            prune_workspace_dependencies(&mut doc, "modules/demo", &candidates, Some(&still_used));
+
+        assert_eq!(removed, vec!["modules/demo", "modules/demo/sdk"]);
+        assert_eq!(pruned, vec!["demo-sdk", "rand"]);
+        assert_eq!(
+            doc.to_string(),
+            r#"[workspace]
+# keep this comment
+members = ["./modules/other/"]
+
+[workspace.dependencies]
+other = { path = "modules/other" }
+tokio = { version = "1" }
+"#
+        );
+    }
+
+    #[test]
+    fn keeps_shared_dependencies_when_usage_is_unknown() {
+        let mut doc = WORKSPACE.parse::<toml_edit::DocumentMut>().expect("toml");
+        let candidates = BTreeSet::from(["rand".to_owned()]);
+
+        let pruned = prune_workspace_dependencies(&mut doc, "modules/demo", &candidates, None);
+
+        assert_eq!(pruned, vec!["demo-sdk"]);
+    }
+
+    #[test]
+    fn collects_workspace_inherited_dependencies() {
+        let doc = r#"
+            [dependencies]
+            tokio = { workspace = true }
+            local = { path = "../local" }
+
+            [dev-dependencies.rand]
+            workspace = true
+
+            [target.'cfg(unix)'.dependencies]
+            nix = { workspace = true, features = ["signal"] }
+        "#
+        .parse::<toml_edit::DocumentMut>()
+        .expect("toml");
+
+        assert_eq!(
+            workspace_deps_used(&doc),
+            BTreeSet::from(["nix".to_owned(), "rand".to_owned(), "tokio".to_owned()])
+        );
+    }
+
+    #[test]
+    fn dependents_lists_remaining_modules_only() {
+        let module = |deps: &[&str]| ConfigModule {
+            metadata: ConfigModuleMetadata {
+                deps: deps.iter().map(|dep| (*dep).to_owned()).collect(),
+                ..ConfigModuleMetadata::default()
+            },
+            config_schema: None,
+        };
+        let local = HashMap::from([
+            ("demo".to_owned(), module(&[])),
+            ("demo-worker".to_owned(), module(&["demo"])),
+            ("reports".to_owned(), module(&["demo", "authz"])),
+        ]);
+        let removed = BTreeSet::from(["demo".to_owned(), "demo-worker".to_owned()]);
+
+        assert_eq!(
+            dependents(&local, &removed),
+            vec![("reports".to_owned(), "demo".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::settings::{ProjectSettings, TemplateSource};
+use anyhow::{Context, bail};
+use clap::{Args, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+#[derive(Args)]
+pub struct TemplatesArgs {
+    #[command(subcommand)]
+    command: TemplatesCommand,
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommand {
+    /// List the module templates offered by each template source
+    List(ListArgs),
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Path to the workspace root (defaults to current directory)
+    #[arg(short = 'p', long, default_value = ".")]
+    path: PathBuf,
+    /// Only list the templates of the named source
+    #[arg(long)]
+    source: Option<String>,
+}
+
+impl TemplatesArgs {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match &self.command {
+            TemplatesCommand::List(args) => args.run(),
+        }
+    }
+}
+
+impl ListArgs {
+    fn run(&self) -> anyhow::Result<()> {
+        let sources = ProjectSettings::load(&self.path)?.module_template_sources();
+        let sources = match &self.source {
+            Some(name) => vec![find_source(&sources, name)?.clone()],
+            None => sources,
+        };
+
+        for (index, source) in sources.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            println!(
+                "{} ({}, {}):",
+                source.name,
+                source.location(),
+                source.subfolder
+            );
+            match list_templates(source) {
+                Ok(templates) if templates.is_empty() => println!("  (none)"),
+                Ok(templates) => {
+                    for template in templates {
+                        println!("  - {template}");
+                    }
+                }
+                Err(err) => println!("  error: {err:#}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn find_source<'a>(
+    sources: &'a [TemplateSource],
+    name: &str,
+) -> anyhow::Result<&'a TemplateSource> {
+    sources
+        .iter()
+        .find(|source| source.name == name)
+        .with_context(|| {
+            let known: Vec<_> = sources.iter().map(|source| source.name.as_str()).collect();
+            format!(
+                "template source '{name}' not found. Known sources: {}",
+                known.join(", ")
+            )
+        })
+}
+
+/// Returns the first source that offers `template`, listing each source in order.
+pub(super) fn find_source_with_template<'a>(
+    sources: &'a [TemplateSource],
+    template: &str,
+) -> anyhow::Result<&'a TemplateSource> {
+    let mut available = Vec::new();
+    for source in sources {
+        match list_templates(source) {
+            Ok(templates) if templates.iter().any(|t| t == template) => return Ok(source),
+            Ok(templates) => available.extend(
+                templates
+                    .into_iter()
+                    .map(|t| format!("{t} ({})", source.name)),
+            ),
+            Err(err) => eprintln!(
+                "warning: can't list templates of source '{}': {err:#}",
+                source.name
+            ),
+        }
+    }
+    bail!(
+        "template '{template}' not found in any template source. Available templates: {}",
+        if available.is_empty() {
+            "(none)".to_owned()
+        } else {
+            available.join(", ")
+        }
+    );
+}
+
+/// Names of the template subfolders of a source, sorted.
+pub(super) fn list_templates(source: &TemplateSource) -> anyhow::Result<Vec<String>> {
+    if let Some(path) = &source.path {
+        return template_dirs(&path.join(&source.subfolder));
+    }
+    let checkout = clone_source(source)?;
+    template_dirs(&checkout.path().join(&source.subfolder))
+}
+
+fn clone_source(source: &TemplateSource) -> anyhow::Result<TempDir> {
+    let Some(git) = &source.git else {
+        bail!(
+            "template source '{}' has neither `git` nor `path`",
+            source.name
+        );
+    };
+    let checkout = tempfile::tempdir().context("can't create temp dir for template checkout")?;
+
+    let mut cmd = Command::new("git");
+    cmd.arg("clone").arg("--quiet").arg("--depth").arg("1");
+    if let Some(branch) = &source.branch {
+        cmd.arg("--branch").arg(branch);
+    }
+    let status = cmd
+        .arg(git)
+        .arg(checkout.path())
+        .stdout(Stdio::null())
+        .status()
+        .context("failed to run git clone")?;
+    if !status.success() {
+        bail!("git clone of {} exited with {status}", source.location());
+    }
+    Ok(checkout)
+}
+
+fn template_dirs(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("can't read templates in {}", dir.display()))?;
+    let mut templates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    templates.sort_unstable();
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_source, find_source_with_template, list_templates};
+    use crate::settings::TemplateSource;
+    use std::fs;
+
+    fn local_source(name: &str, root: &std::path::Path) -> TemplateSource {
+        TemplateSource {
+            name: name.to_owned(),
+            git: None,
+            branch: None,
+            path: Some(root.to_path_buf()),
+            subfolder: "Modules".to_owned(),
+        }
+    }
+
+    #[test]
+    fn lists_template_subfolders_of_local_source() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        for dir in ["kafka-consumer", "grpc-service", ".git"] {
+            fs::create_dir_all(temp_dir.path().join("Modules").join(dir)).expect("create dir");
+        }
+        fs::write(temp_dir.path().join("Modules/README.md"), "").expect("write file");
+
+        let templates =
+            list_templates(&local_source("acme", temp_dir.path())).expect("templates listed");
+
+        assert_eq!(templates, vec!["grpc-service", "kafka-consumer"]);
+    }
+
+    #[test]
+    fn finds_first_source_offering_the_template() {
+        let first = tempfile::tempdir().expect("temp dir");
+        let second = tempfile::tempdir().expect("temp dir");
+        fs::create_dir_all(first.path().join("Modules/kafka-consumer")).expect("create dir");
+        fs::create_dir_all(second.path().join("Modules/grpc-service")).expect("create dir");
+        let sources = vec![
+            local_source("first", first.path()),
+            local_source("second", second.path()),
+        ];
+
+        let source = find_source_with_template(&sources, "grpc-service").expect("template found");
+        assert_eq!(source.name, "second");
+
+        let err = find_source_with_template(&sources, "missing").expect_err("unknown template");
+        assert!(err.to_string().contains("kafka-consumer (first)"));
+        assert!(find_source(&sources, "third").is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! Terminal dashboard for `run --watch --tui`.
+//!
+//! The server runner and the `OoP` module supervisors [`report`] what their
+//! process is doing. While the dashboard is active, their output is captured
+//! into a log pane per process instead of being printed, and its hotkeys send
+//! [`RunSignal`]s to the process's runner.
+
+use super::process;
+use super::run_loop::RunSignal;
+use anyhow::{Context, bail};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::IsTerminal;
+use std::sync::{Mutex, OnceLock, mpsc};
+use std::time::Duration;
+
+/// Name the generated server is reported under.
+pub(super) const SERVER: &str = "server";
+/// Log lines kept per process.
+const LOG_CAPACITY: usize = 5000;
+
+/// What a runner reports about its process.
+pub(super) enum Update {
+    Building,
+    /// The build result, with the error when it failed.
+    Built(Result<(), String>),
+    Running,
+    Exited(String),
+    Log(String),
+}
+
+static UPDATES: OnceLock<mpsc::Sender<(String, Update)>> = OnceLock::new();
+/// Signal senders of the runners, by process name.
+static CONTROLS: Mutex<BTreeMap<String, mpsc::Sender<RunSignal>>> = Mutex::new(BTreeMap::new());
+
+pub(super) fn is_active() -> bool {
+    UPDATES.get().is_some()
+}
+
+/// Sends `update` to the dashboard. Returns `false` when it isn't active.
+pub(super) fn report(process: &str, update: Update) -> bool {
+    UPDATES
+        .get()
+        .is_some_and(|updates| updates.send((process.to_owned(), update)).is_ok())
+}
+
+/// A message about `process`: a line of its log pane on the dashboard,
+/// otherwise printed to stderr, prefixed with the name of `OoP` modules.
+pub(super) fn message(process: &str, text: &str) {
+    if report(process, Update::Log(text.to_owned())) {
+        return;
+    }
+    if process == SERVER {
+        eprintln!("{text}");
+    } else {
+        eprintln!("[{process}] {text}");
+    }
+}
+
+/// Lets the dashboard hotkeys signal the runner of `process`.
+pub(super) fn register(process: &str, signals: mpsc::Sender<RunSignal>) {
+    if let Ok(mut controls) = CONTROLS.lock() {
+        controls.insert(process.to_owned(), signals);
+    }
+}
+
+/// Takes over the terminal until the user quits, which stops every process.
+pub(super) fn start() -> anyhow::Result<()> {
+    if !std::io::stdout().is_terminal() {
+        bail!("--tui needs an interactive terminal");
+    }
+    let (tx, rx) = mpsc::channel();
+    if UPDATES.set(tx).is_err() {
+        return Ok(());
+    }
+    let terminal = ratatui::try_init().context("failed to set up the terminal")?;
+    std::thread::spawn(move || {
+        let result = Dashboard::default().run(terminal, &rx);
+        ratatui::restore();
+        if let Err(err) = result {
+            eprintln!("dashboard failed: {err}");
+        }
+        process::shutdown(0);
+    });
+    Ok(())
+}
+
+#[derive(Default)]
+enum Status {
+    #[default]
+    Starting,
+    Building,
+    BuildFailed,
+    Running,
+    Exited(String),
+}
+
+#[derive(Default)]
+struct ProcessView {
+    status: Status,
+    /// `None` until the first build finished.
+    last_build: Option<bool>,
+    starts: u32,
+    logs: VecDeque<String>,
+    /// Lines scrolled up from the end of the log.
+    scroll: usize,
+}
+
+impl ProcessView {
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Building => self.status = Status::Building,
+            Update::Built(result) => {
+                self.last_build = Some(result.is_ok());
+                if let Err(err) = result {
+                    self.status = Status::BuildFailed;
+                    self.push_log(err);
+                }
+            }
+            Update::Running => {
+                self.status = Status::Running;
+                self.starts += 1;
+            }
+            Update::Exited(reason) => {
+                self.push_log(reason.clone());
+                self.status = Status::Exited(reason);
+            }
+            Update::Log(line) => self.push_log(line),
+        }
+    }
+
+    fn push_log(&mut self, line: String) {
+        if self.logs.len() == LOG_CAPACITY {
+            self.logs.pop_front();
+        }
+        self.logs.push_back(line);
+        if self.scroll > 0 {
+            // Keep the view still while following is paused.
+            self.scroll = (self.scroll + 1).min(self.logs.len());
+        }
+    }
+
+    /// The log lines that fit `height`, honouring the scroll position.
+    fn visible_logs(&self, height: usize) -> impl Iterator<Item = &String> {
+        let end = self.logs.len().saturating_sub(self.scroll);
+        self.logs.range(end.saturating_sub(height)..end)
+    }
+}
+
+#[derive(Default)]
+struct Dashboard {
+    processes: BTreeMap<String, ProcessView>,
+    selected: usize,
+}
+
+impl Dashboard {
+    fn run(
+        &mut self,
+        mut terminal: DefaultTerminal,
+        updates: &mpsc::Receiver<(String, Update)>,
+    ) -> anyhow::Result<()> {
+        self.processes.entry(SERVER.to_owned()).or_default();
+        loop {
+            while let Ok((process, update)) = updates.try_recv() {
+                self.processes.entry(process).or_default().apply(update);
+            }
+            terminal
+                .draw(|frame| self.draw(frame))
+                .context("failed to draw the dashboard")?;
+
+            if !event::poll(Duration::from_millis(100)).context("failed to read input")? {
+                continue;
+            }
+            let Event::Key(key) = event::read().context("failed to read input")? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let page = usize::from(terminal.size().map_or(20, |size| size.height / 2));
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Char('b') => self.signal_selected(RunSignal::Rerun),
+                KeyCode::Char('r') => self.signal_selected(RunSignal::Restart),
+                KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.selected = (self.selected + 1).min(self.processes.len() - 1);
+                }
+                KeyCode::PageUp => self.scroll_selected(|view| {
+                    view.scroll = (view.scroll + page).min(view.logs.len());
+                }),
+                KeyCode::PageDown => self.scroll_selected(|view| {
+                    view.scroll = view.scroll.saturating_sub(page);
+                }),
+                KeyCode::End => self.scroll_selected(|view| view.scroll = 0),
+                _ => {}
+            }
+        }
+    }
+
+    fn selected_name(&self) -> Option<&String> {
+        self.processes.keys().nth(self.selected)
+    }
+
+    fn signal_selected(&self, signal: RunSignal) {
+        let Some(name) = self.selected_name() else {
+            return;
+        };
+        if let Ok(controls) = CONTROLS.lock()
+            && let Some(signals) = controls.get(name)
+        {
+            _ = signals.send(signal);
+        }
+    }
+
+    fn scroll_selected(&mut self, scroll: impl FnOnce(&mut ProcessView)) {
+        if let Some(view) = self.processes.values_mut().nth(self.selected) {
+            scroll(view);
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let table_height = u16::try_from(self.processes.len()).unwrap_or(u16::MAX);
+        let [table_area, log_area, help_area] = Layout::vertical([
+            Constraint::Length(table_height.saturating_add(3)),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_table(frame, table_area);
+        self.draw_logs(frame, log_area);
+        frame.render_widget(
+            Paragraph::new(
+                "↑/↓ select  PgUp/PgDn scroll  End follow  b rebuild  r restart  q quit",
+            )
+            .style(Style::default().fg(Color::DarkGray)),
+            help_area,
+        );
+    }
+
+    fn draw_table(&self, frame: &mut Frame, area: Rect) {
+        let rows = self.processes.iter().map(|(name, view)| {
+            let (status, color) = match &view.status {
+                Status::Starting => ("starting".to_owned(), Color::Gray),
+                Status::Building => ("building".to_owned(), Color::Yellow),
+                Status::BuildFailed => ("build failed".to_owned(), Color::Red),
+                Status::Running => ("running".to_owned(), Color::Green),
+                Status::Exited(reason) => (reason.clone(), Color::Red),
+            };
+            let build = match view.last_build {
+                None => "-",
+                Some(true) => "ok",
+                Some(false) => "failed",
+            };
+            Row::new(vec![
+                Cell::from(name.as_str()),
+                Cell::from(status).style(Style::default().fg(color)),
+                Cell::from(build),
+                Cell::from(view.starts.saturating_sub(1).to_string()),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Min(16),
+                Constraint::Min(24),
+                Constraint::Length(10),
+                Constraint::Length(8),
+            ],
+        )
+        .header(
+            Row::new(["process", "status", "build", "restarts"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(" cyberfabric run "));
+        let mut state = TableState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    fn draw_logs(&self, frame: &mut Frame, area: Rect) {
+        let Some((name, view)) = self.processes.iter().nth(self.selected) else {
+            return;
+        };
+        let height = usize::from(area.height.saturating_sub(2));
+        let lines: Vec<Line> = view
+            .visible_logs(height)
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        let title = if view.scroll == 0 {
+            format!(" {name} ")
+        } else {
+            format!(" {name} (scrolled up {} lines) ", view.scroll)
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProcessView, Status, Update};
+
+    #[test]
+    fn tracks_builds_restarts_and_logs() {
+        let mut view = ProcessView::default();
+        for update in [
+            Update::Building,
+            Update::Built(Ok(())),
+            Update::Running,
+            Update::Log("listening on :8080".to_owned()),
+            Update::Exited("exited with exit status: 1".to_owned()),
+            Update::Running,
+            Update::Building,
+            Update::Built(Err("cargo build exited with exit status: 101".to_owned())),
+        ] {
+            view.apply(update);
+        }
+
+        assert!(matches!(view.status, Status::BuildFailed));
+        assert_eq!(view.last_build, Some(false));
+        assert_eq!(view.starts, 2);
+        assert_eq!(
+            view.visible_logs(2).collect::<Vec<_>>(),
+            [
+                "exited with exit status: 1",
+                "cargo build exited with exit status: 101"
+            ]
+        );
+    }
+
+    #[test]
+    fn scrolled_logs_stay_in_place() {
+        let mut view = ProcessView::default();
+        for line in 0..10 {
+            view.apply(Update::Log(line.to_string()));
+        }
+        view.scroll = 3;
+
+        view.apply(Update::Log("10".to_owned()));
+
+        assert_eq!(view.visible_logs(2).collect::<Vec<_>>(), ["5", "6"]);
+    }
+}
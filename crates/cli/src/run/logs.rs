@@ -0,0 +1,353 @@
+//! Pretty-printing and filtering of the structured logs written by the
+//! processes `run` starts.
+//!
+//! Lines in the JSON format of `tracing-subscriber` are rendered as
+//! `time LEVEL [module] target: message key=value` and dropped when the
+//! `--log-filter` rejects them. Other lines are passed through as they are.
+
+use anstyle::{AnsiColor, Style};
+use anyhow::bail;
+use serde_json::{Map, Value};
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// The `--log-filter` of this run, if any.
+pub(super) static FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// Keys of a JSON log line that aren't rendered as fields.
+const RESERVED_KEYS: &[&str] = &[
+    "timestamp",
+    "level",
+    "target",
+    "fields",
+    "message",
+    "span",
+    "spans",
+    "filename",
+    "line_number",
+    "threadName",
+    "threadId",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+
+    const fn style(self) -> Style {
+        match self {
+            Self::Trace => AnsiColor::Magenta.on_default(),
+            Self::Debug => AnsiColor::Blue.on_default(),
+            Self::Info => AnsiColor::Green.on_default(),
+            Self::Warn => AnsiColor::Yellow.on_default(),
+            Self::Error => AnsiColor::Red.on_default().bold(),
+        }
+    }
+}
+
+/// Parses a level name; `off` is `None`.
+fn parse_level(name: &str) -> anyhow::Result<Option<Level>> {
+    Ok(Some(match name.to_ascii_lowercase().as_str() {
+        "off" => return Ok(None),
+        "trace" => Level::Trace,
+        "debug" => Level::Debug,
+        "info" => Level::Info,
+        "warn" | "warning" => Level::Warn,
+        "error" => Level::Error,
+        _ => bail!("unknown log level '{name}', expected trace, debug, info, warn, error or off"),
+    }))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Selector {
+    Module(String),
+    /// A target and the targets nested in it, such as `sqlx` for `sqlx::query`.
+    Target(String),
+}
+
+/// Comma-separated directives: a bare level sets the default, `<target>=<level>`
+/// and `module:<name>=<level>` override it, and `module:<name>` without a level
+/// shows all of its lines. Once any selector is given, lines it doesn't match
+/// are hidden unless a default level is set.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct LogFilter {
+    /// Threshold of the lines no directive selects; `None` hides them.
+    default: Option<Level>,
+    directives: Vec<(Selector, Option<Level>)>,
+}
+
+impl FromStr for LogFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(filter: &str) -> anyhow::Result<Self> {
+        let mut default = None;
+        let mut directives = Vec::new();
+        for directive in filter.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (selector, level) = match directive.split_once('=') {
+                Some((selector, level)) => (selector.trim(), parse_level(level.trim())?),
+                // A bare word is a level, so that a typo isn't taken for a
+                // target and hides every line.
+                None if directive.starts_with("module:") => (directive, Some(Level::Trace)),
+                None => {
+                    let Ok(level) = parse_level(directive) else {
+                        bail!(
+                            "unknown log level '{directive}', expected trace, debug, info, warn, error or off; use `<target>=<level>` for a target"
+                        );
+                    };
+                    default = Some(level);
+                    continue;
+                }
+            };
+            let selector = selector.strip_prefix("module:").map_or_else(
+                || Selector::Target(selector.to_owned()),
+                |module| Selector::Module(module.to_owned()),
+            );
+            directives.push((selector, level));
+        }
+        Ok(Self {
+            // Selectors alone only show what they match.
+            default: default.unwrap_or_else(|| directives.is_empty().then_some(Level::Trace)),
+            directives,
+        })
+    }
+}
+
+impl LogFilter {
+    /// Joins the directives of every `--log-filter`.
+    pub(super) fn parse_all(filters: &[String]) -> anyhow::Result<Self> {
+        filters.join(",").parse()
+    }
+
+    fn allows(&self, record: &Record) -> bool {
+        let module = record.module.as_deref().and_then(|module| {
+            self.directives
+                .iter()
+                .find_map(|(selector, level)| match selector {
+                    Selector::Module(name) if name == module => Some(*level),
+                    _ => None,
+                })
+        });
+        let target = || {
+            self.directives
+                .iter()
+                .filter_map(|(selector, level)| match selector {
+                    Selector::Target(target) if is_within(&record.target, target) => {
+                        Some((target.len(), *level))
+                    }
+                    _ => None,
+                })
+                .max_by_key(|(len, _)| *len)
+                .map(|(_, level)| level)
+        };
+        let threshold = module.or_else(target).unwrap_or(self.default);
+        threshold.is_some_and(|threshold| record.level >= threshold)
+    }
+}
+
+/// Whether `target` is `parent` or one of its submodules.
+fn is_within(target: &str, parent: &str) -> bool {
+    target
+        .strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// A structured log line.
+#[derive(Debug, PartialEq, Eq)]
+struct Record {
+    timestamp: Option<String>,
+    level: Level,
+    target: String,
+    module: Option<String>,
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Record {
+    fn parse(line: &str) -> Option<Self> {
+        let Value::Object(json) = serde_json::from_str(line).ok()? else {
+            return None;
+        };
+        let level = json
+            .get("level")
+            .and_then(Value::as_str)
+            .and_then(|level| parse_level(level).ok().flatten())?;
+        // Fields are either nested, as `tracing-subscriber` does by default,
+        // or flattened into the line.
+        let fields = json
+            .get("fields")
+            .and_then(Value::as_object)
+            .unwrap_or(&json);
+        let message = fields.get("message").map(text).unwrap_or_default();
+        let module = ["module", "module_name"]
+            .iter()
+            .find_map(|key| fields.get(*key).or_else(|| span_field(&json, key)))
+            .map(text);
+        Some(Self {
+            timestamp: json
+                .get("timestamp")
+                .and_then(Value::as_str)
+                .map(short_time),
+            level,
+            target: json.get("target").map(text).unwrap_or_default(),
+            module,
+            message,
+            fields: fields
+                .iter()
+                .filter(|(key, _)| !RESERVED_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), text(value)))
+                .collect(),
+        })
+    }
+
+    fn render(&self, color: bool) -> String {
+        let paint = |style: Style, text: &str| {
+            if color {
+                format!("{style}{text}{style:#}")
+            } else {
+                text.to_owned()
+            }
+        };
+        let dim = Style::new().dimmed();
+        let mut out = String::new();
+        if let Some(timestamp) = &self.timestamp {
+            let _ = write!(out, "{} ", paint(dim, timestamp));
+        }
+        let _ = write!(
+            out,
+            "{} ",
+            paint(self.level.style(), &format!("{:>5}", self.level.name()))
+        );
+        if let Some(module) = &self.module {
+            let _ = write!(out, "[{}] ", paint(AnsiColor::Cyan.on_default(), module));
+        }
+        if !self.target.is_empty() {
+            let _ = write!(out, "{} ", paint(dim, &format!("{}:", self.target)));
+        }
+        out.push_str(&self.message);
+        for (key, value) in &self.fields {
+            let _ = write!(
+                out,
+                " {}{value}",
+                paint(Style::new().italic(), &format!("{key}="))
+            );
+        }
+        out
+    }
+}
+
+/// Looks `key` up in the current span, then in the outer ones.
+fn span_field<'a>(json: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+    json.get("span").and_then(|span| span.get(key)).or_else(|| {
+        json.get("spans")
+            .and_then(Value::as_array)
+            .and_then(|spans| spans.iter().rev().find_map(|span| span.get(key)))
+    })
+}
+
+fn text(value: &Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.to_string(), ToOwned::to_owned)
+}
+
+/// Keeps the time of day of an RFC 3339 timestamp, to milliseconds.
+fn short_time(timestamp: &str) -> String {
+    timestamp
+        .split_once('T')
+        .map_or(timestamp, |(_, time)| time.get(..12).unwrap_or(time))
+        .to_owned()
+}
+
+/// Renders a line of output of `process`, or returns `None` when the
+/// `--log-filter` hides it. `OoP` modules count as the module of their lines.
+pub(super) fn render(line: String, process: Option<&str>, color: bool) -> Option<String> {
+    let Some(mut record) = Record::parse(&line) else {
+        return Some(line);
+    };
+    if record.module.is_none() {
+        record.module = process.map(ToOwned::to_owned);
+    }
+    FILTER
+        .get()
+        .is_none_or(|filter| filter.allows(&record))
+        .then(|| record.render(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Level, LogFilter, Record};
+
+    fn record(level: Level, target: &str, module: Option<&str>) -> Record {
+        Record {
+            timestamp: None,
+            level,
+            target: target.to_owned(),
+            module: module.map(ToOwned::to_owned),
+            message: String::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parses_tracing_json_lines() {
+        let line = r#"{"timestamp":"2026-03-01T10:15:30.123456Z","level":"WARN","fields":{"message":"slow query","elapsed_ms":812},"target":"sqlx::query","spans":[{"module":"users","name":"module"}]}"#;
+
+        let record = Record::parse(line).expect("a log line");
+
+        assert_eq!(
+            record,
+            Record {
+                timestamp: Some("10:15:30.123".to_owned()),
+                level: Level::Warn,
+                target: "sqlx::query".to_owned(),
+                module: Some("users".to_owned()),
+                message: "slow query".to_owned(),
+                fields: vec![("elapsed_ms".to_owned(), "812".to_owned())],
+            }
+        );
+        assert_eq!(
+            record.render(false),
+            "10:15:30.123  WARN [users] sqlx::query: slow query elapsed_ms=812"
+        );
+        assert!(Record::parse("Listening on 0.0.0.0:8080").is_none());
+        assert!(Record::parse(r#"{"status":"ok"}"#).is_none());
+    }
+
+    #[test]
+    fn filters_by_level_target_and_module() {
+        let filter: LogFilter = "warn,sqlx=error,api_gateway::router=debug,module:users"
+            .parse()
+            .expect("valid filter");
+
+        assert!(!filter.allows(&record(Level::Info, "modkit::runtime", None)));
+        assert!(filter.allows(&record(Level::Warn, "modkit::runtime", None)));
+        assert!(!filter.allows(&record(Level::Warn, "sqlx::query", None)));
+        assert!(filter.allows(&record(Level::Debug, "api_gateway::router::v1", None)));
+        assert!(!filter.allows(&record(Level::Debug, "api_gateway::routerx", None)));
+        assert!(filter.allows(&record(Level::Trace, "sqlx::query", Some("users"))));
+
+        let only_users: LogFilter = "module:users".parse().expect("valid filter");
+        assert!(only_users.allows(&record(Level::Debug, "users::db", Some("users"))));
+        assert!(!only_users.allows(&record(Level::Error, "orders", Some("orders"))));
+
+        assert!("verbose".parse::<LogFilter>().is_err());
+        assert!("warnn".parse::<LogFilter>().is_err());
+        assert!("sqlx=loud".parse::<LogFilter>().is_err());
+    }
+}
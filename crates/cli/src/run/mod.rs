@@ -1,32 +1,97 @@
+mod dashboard;
+mod logs;
+mod oop;
+mod process;
 mod run_loop;
+mod watch_filter;
 
-use crate::common::BuildRunArgs;
+use crate::common::{self, BuildRunArgs};
 use crate::run::run_loop::RunSignal;
+use anyhow::Context;
 use clap::Args;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::time::Duration;
 
 #[derive(Args)]
 pub struct RunArgs {
     /// Watch for changes
     #[arg(short = 'w', long)]
     watch: bool,
+    /// Quiet period in milliseconds that ends a burst of file changes in watch mode
+    #[arg(long, value_name = "MS", default_value_t = 300, requires = "watch")]
+    debounce: u64,
+    /// Seconds a server gets to shut down after SIGTERM before it is killed in watch mode
+    #[arg(long, value_name = "SECS", default_value_t = 10, requires = "watch")]
+    grace_period: u64,
+    /// Glob of paths whose changes don't trigger a restart in watch mode; `.gitignore` files are honoured too
+    #[arg(long = "ignore", value_name = "GLOB", requires = "watch")]
+    ignore: Vec<String>,
+    /// Extra directory to watch, such as migrations or static assets outside module crates
+    #[arg(long = "watch-path", value_name = "PATH", requires = "watch")]
+    watch_paths: Vec<PathBuf>,
+    /// Build the workspace's out-of-process modules and run them next to the server, restarting them when they crash
+    #[arg(long)]
+    oop: bool,
+    /// Show a terminal dashboard with the status and logs of every process in watch mode
+    #[arg(long, requires = "watch")]
+    tui: bool,
+    /// Only show structured log lines matching these comma-separated directives: a default level (`warn`), `<target>=<level>` or `module:<name>[=<level>]`
+    #[arg(long = "log-filter", value_name = "FILTER")]
+    log_filters: Vec<String>,
     #[command(flatten)]
     br_args: BuildRunArgs,
 }
 
 impl RunArgs {
     pub fn run(&self) -> anyhow::Result<()> {
-        let (config_path, project_name) = self.br_args.resolve_config_and_name()?;
+        let (layers, project_name) = self.br_args.resolve_config_and_name()?;
+        let flags = self.br_args.flags()?;
 
-        let rl = run_loop::RunLoop::new(config_path, project_name);
-        run_loop::OTEL.store(self.br_args.otel, std::sync::atomic::Ordering::Relaxed);
-        run_loop::FIPS.store(self.br_args.fips, std::sync::atomic::Ordering::Relaxed);
-        run_loop::RELEASE.store(self.br_args.release, std::sync::atomic::Ordering::Relaxed);
+        let rl = run_loop::RunLoop::new(layers, project_name, self.br_args.env.vars()?, self.oop);
+        run_loop::OTEL.store(flags.otel, std::sync::atomic::Ordering::Relaxed);
+        run_loop::FIPS.store(flags.fips, std::sync::atomic::Ordering::Relaxed);
+        run_loop::RELEASE.store(flags.release, std::sync::atomic::Ordering::Relaxed);
+        process::GRACE_PERIOD.store(self.grace_period, std::sync::atomic::Ordering::Relaxed);
+
+        if !self.log_filters.is_empty() {
+            let filter =
+                logs::LogFilter::parse_all(&self.log_filters).context("invalid --log-filter")?;
+            _ = logs::FILTER.set(filter);
+        }
+
+        let watch = self.watch.then(|| run_loop::WatchOptions {
+            debounce: Duration::from_millis(self.debounce),
+            ignore: self.ignore.clone(),
+            paths: self.watch_paths.clone(),
+        });
+
+        if self.tui {
+            dashboard::start()?;
+        }
 
         loop {
-            match rl.run(self.watch)? {
-                RunSignal::Rerun => {}
+            match rl.run(watch.as_ref())? {
+                RunSignal::Rerun | RunSignal::Restart => {}
                 RunSignal::Stop => break Ok(()),
             }
         }
     }
 }
+
+/// Builds the generated server in `cargo_dir` and starts its executable in its
+/// own process group, as `run` does. Stop it with [`stop_server`]; Ctrl+C
+/// stops it as well.
+pub fn start_server(cargo_dir: &Path, config_path: &Path, release: bool) -> anyhow::Result<Child> {
+    process::install_interrupt_handler();
+    let executable = process::build_executable(
+        dashboard::SERVER,
+        common::cargo_command("build", cargo_dir, config_path, false, false, release)?,
+    )?;
+    run_loop::spawn_server(&executable, cargo_dir, config_path)
+}
+
+/// Stops a server started by [`start_server`] with every process it spawned.
+pub fn stop_server(server: Child) {
+    process::stop(server);
+}
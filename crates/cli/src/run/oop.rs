@@ -0,0 +1,345 @@
+//! Local supervision of out-of-process modules (`run --oop`).
+//!
+//! `OoP` modules that live in the workspace are built with Cargo and started by
+//! the CLI instead of modkit, so their `runtime.execution` is removed from the
+//! config handed to the server. Each module runs in its own thread: it is
+//! restarted with a backoff when it crashes, rebuilt and restarted when a
+//! [`RunSignal::Rerun`] arrives, and restarted as is on [`RunSignal::Restart`].
+
+use super::dashboard::{self, Update};
+use super::process;
+use super::run_loop::{RELEASE, RunSignal};
+use crate::app_config::{ExecutionConfig, ModuleRuntime, RuntimeKind};
+use crate::common;
+use anyhow::Context;
+use module_parser::ConfigModule;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A module that ran this long before crashing restarts without backoff.
+const STABLE_RUN: Duration = Duration::from_secs(30);
+
+/// An `OoP` module of the workspace, started by the CLI.
+#[derive(Clone, PartialEq)]
+pub(super) struct OopModule {
+    name: String,
+    package: String,
+    /// Crate directory, whose changes rebuild the module in watch mode.
+    dir: Option<PathBuf>,
+    execution: ExecutionConfig,
+}
+
+impl OopModule {
+    pub(super) fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+}
+
+/// Takes the `OoP` modules that are workspace members out of the config's
+/// process management, returning them with their execution settings.
+pub(super) fn detach(
+    config: &mut Value,
+    members: &HashMap<String, ConfigModule>,
+) -> anyhow::Result<Vec<OopModule>> {
+    let Some(modules) = config.get_mut("modules").and_then(Value::as_object_mut) else {
+        return Ok(Vec::new());
+    };
+    let mut detached = Vec::new();
+    for (name, module) in modules {
+        let Some(member) = members.get(name) else {
+            continue;
+        };
+        let Some(runtime) = module.get_mut("runtime") else {
+            continue;
+        };
+        let parsed: ModuleRuntime = serde_json::from_value(runtime.clone())
+            .with_context(|| format!("invalid runtime of module '{name}'"))?;
+        if !matches!(parsed.mod_type, RuntimeKind::Oop) {
+            continue;
+        }
+        let Some(package) = member.metadata.package.clone() else {
+            continue;
+        };
+        if let Some(runtime) = runtime.as_object_mut() {
+            runtime.remove("execution");
+        }
+        detached.push(OopModule {
+            name: name.clone(),
+            package,
+            dir: member.metadata.path.as_ref().map(PathBuf::from),
+            execution: parsed.execution.unwrap_or_default(),
+        });
+    }
+    Ok(detached)
+}
+
+/// The supervision threads of the detached modules.
+pub(super) struct Supervisor {
+    modules: Vec<(OopModule, mpsc::Sender<RunSignal>, JoinHandle<()>)>,
+}
+
+impl Supervisor {
+    pub(super) fn start(modules: Vec<OopModule>, workspace: &Path) -> Self {
+        let modules = modules
+            .into_iter()
+            .map(|module| {
+                let (tx, rx) = mpsc::channel();
+                dashboard::register(&module.name, tx.clone());
+                let workspace = workspace.to_path_buf();
+                let supervised = module.clone();
+                let handle = std::thread::spawn(move || supervise(&supervised, &workspace, &rx));
+                (module, tx, handle)
+            })
+            .collect();
+        Self { modules }
+    }
+
+    /// Rebuilds and restarts the modules whose crate contains one of `paths`.
+    pub(super) fn restart_changed(&self, paths: &[&Path]) {
+        for (module, tx, _) in &self.modules {
+            if module
+                .dir()
+                .is_some_and(|dir| paths.iter().any(|path| path.starts_with(dir)))
+            {
+                _ = tx.send(RunSignal::Rerun);
+            }
+        }
+    }
+}
+
+/// Stops every module and waits for its supervision thread.
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        for (_, tx, _) in &self.modules {
+            _ = tx.send(RunSignal::Stop);
+        }
+        for (module, _, handle) in self.modules.drain(..) {
+            if handle.join().is_err() {
+                dashboard::message(&module.name, "supervisor panicked");
+            }
+        }
+    }
+}
+
+/// What ended the wait on a module.
+enum Outcome {
+    Signal(RunSignal),
+    Exited(String),
+}
+
+/// Like the server in watch mode, a rebuilt module only replaces the running
+/// one once the build succeeded.
+fn supervise(module: &OopModule, workspace: &Path, signals: &mpsc::Receiver<RunSignal>) {
+    let mut executable = None;
+    let mut child: Option<Child> = None;
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let mut outcome = match build(module, workspace) {
+            Ok(built) => {
+                if let Some(running) = child.take() {
+                    process::stop(running);
+                }
+                backoff = MIN_BACKOFF;
+                let started = spawn(module, &built, workspace);
+                executable = Some(built);
+                match started {
+                    Ok(started) => {
+                        child = Some(started);
+                        None
+                    }
+                    Err(e) => Some(Outcome::Exited(format!("{e:#}"))),
+                }
+            }
+            Err(e) if child.is_some() => {
+                dashboard::message(
+                    &module.name,
+                    &format!("{e:#}, the running instance is kept until the next successful build"),
+                );
+                None
+            }
+            Err(e) => {
+                dashboard::message(&module.name, &format!("{e:#}"));
+                None
+            }
+        };
+
+        let mut started_at = Instant::now();
+        loop {
+            match outcome.take().unwrap_or_else(|| wait(&mut child, signals)) {
+                Outcome::Signal(RunSignal::Rerun) => break,
+                Outcome::Signal(RunSignal::Restart) => {
+                    if let Some(running) = child.take() {
+                        process::stop(running);
+                    }
+                    backoff = MIN_BACKOFF;
+                    started_at = Instant::now();
+                    outcome = restart(module, executable.as_deref(), workspace, &mut child);
+                }
+                Outcome::Signal(RunSignal::Stop) => {
+                    if let Some(running) = child.take() {
+                        process::stop(running);
+                    }
+                    return;
+                }
+                Outcome::Exited(reason) => {
+                    if started_at.elapsed() >= STABLE_RUN {
+                        backoff = MIN_BACKOFF;
+                    }
+                    dashboard::report(&module.name, Update::Exited(reason.clone()));
+                    dashboard::message(
+                        &module.name,
+                        &format!("{reason}, restarting in {}s", backoff.as_secs()),
+                    );
+                    match signals.recv_timeout(backoff) {
+                        Ok(RunSignal::Rerun) => break,
+                        Ok(RunSignal::Restart) => backoff = MIN_BACKOFF,
+                        Ok(RunSignal::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                            return;
+                        }
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                    }
+                    started_at = Instant::now();
+                    outcome = restart(module, executable.as_deref(), workspace, &mut child);
+                }
+            }
+        }
+    }
+}
+
+/// Starts the last built executable again, if any.
+fn restart(
+    module: &OopModule,
+    executable: Option<&Path>,
+    workspace: &Path,
+    child: &mut Option<Child>,
+) -> Option<Outcome> {
+    match spawn(module, executable?, workspace) {
+        Ok(started) => {
+            *child = Some(started);
+            None
+        }
+        Err(e) => Some(Outcome::Exited(format!("{e:#}"))),
+    }
+}
+
+fn build(module: &OopModule, workspace: &Path) -> anyhow::Result<PathBuf> {
+    let mut cmd = common::cargo_cmd()?;
+    cmd.args(["build", "-p", &module.package])
+        .current_dir(workspace);
+    if RELEASE.load(Ordering::Relaxed) {
+        cmd.arg("-r");
+    }
+    process::build_executable(&module.name, cmd)
+        .with_context(|| format!("failed to build package '{}'", module.package))
+}
+
+/// Starts the module with its execution settings, prefixing its output lines
+/// with the module name.
+fn spawn(module: &OopModule, executable: &Path, workspace: &Path) -> anyhow::Result<Child> {
+    let execution = &module.execution;
+    let working_directory = execution
+        .working_directory
+        .as_ref()
+        .map_or_else(|| workspace.to_path_buf(), |dir| workspace.join(dir));
+    let mut cmd = Command::new(executable);
+    cmd.args(&execution.args)
+        .envs(&execution.environment)
+        .current_dir(working_directory)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    process::spawn_in_group(&module.name, &mut cmd, executable)
+}
+
+/// Waits until the running module exits or a signal arrives. Without a
+/// running module, only waits for a signal.
+fn wait(child: &mut Option<Child>, signals: &mpsc::Receiver<RunSignal>) -> Outcome {
+    loop {
+        if let Some(running) = child {
+            let exited = match running.try_wait() {
+                Ok(Some(status)) => Some(format!("exited with {status}")),
+                Ok(None) => None,
+                Err(e) => Some(format!("can't check its status: {e}")),
+            };
+            if let Some(reason) = exited {
+                if let Some(running) = child.take() {
+                    process::stop(running);
+                }
+                return Outcome::Exited(reason);
+            }
+        }
+        match signals.recv_timeout(Duration::from_millis(100)) {
+            Ok(signal) => return Outcome::Signal(signal),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Outcome::Signal(RunSignal::Stop),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detach;
+    use module_parser::{ConfigModule, ConfigModuleMetadata};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    #[test]
+    fn detaches_workspace_oop_modules_only() {
+        let mut config = json!({
+            "modules": {
+                "worker": { "runtime": { "type": "oop", "execution": {
+                    "executable_path": "~/bin/worker",
+                    "args": ["--queue", "jobs"],
+                    "environment": { "RUST_LOG": "debug" }
+                } } },
+                "remote": { "runtime": { "type": "oop", "execution": {
+                    "executable_path": "/opt/remote"
+                } } },
+                "api": { "config": {} }
+            }
+        });
+        let members = ["worker", "api"]
+            .into_iter()
+            .map(|name| {
+                (
+                    name.to_owned(),
+                    ConfigModule {
+                        metadata: ConfigModuleMetadata {
+                            package: Some(format!("cf-{name}")),
+                            path: Some(format!("/ws/modules/{name}")),
+                            ..ConfigModuleMetadata::default()
+                        },
+                        config_schema: None,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let detached = detach(&mut config, &members).expect("runtime parses");
+
+        assert_eq!(detached.len(), 1);
+        let worker = &detached[0];
+        assert_eq!(
+            (worker.name.as_str(), worker.package.as_str()),
+            ("worker", "cf-worker")
+        );
+        assert_eq!(worker.dir(), Some(Path::new("/ws/modules/worker")));
+        assert_eq!(worker.execution.args, ["--queue", "jobs"]);
+        assert_eq!(worker.execution.environment["RUST_LOG"], "debug");
+        assert_eq!(
+            config["modules"]["worker"]["runtime"],
+            json!({ "type": "oop" })
+        );
+        assert!(config["modules"]["remote"]["runtime"]["execution"].is_object());
+    }
+}
@@ -0,0 +1,304 @@
+//! Child processes started by `run`: building executables with Cargo, and
+//! running them in their own process group so that stopping one also reaches
+//! the processes it spawned.
+
+use super::dashboard::{self, Update};
+use super::logs;
+use anyhow::Context;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Seconds a stopping process gets between SIGTERM and SIGKILL.
+pub(super) static GRACE_PERIOD: AtomicU64 = AtomicU64::new(10);
+/// Process groups started by [`spawn_in_group`] that are still running.
+static GROUPS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Runs a `cargo build` command for `process` and returns the executable it
+/// built. Compiler output is rendered to stderr as usual, or to the process's
+/// log pane on the dashboard.
+pub(super) fn build_executable(process: &str, mut cargo_build: Command) -> anyhow::Result<PathBuf> {
+    dashboard::report(process, Update::Building);
+    cargo_build
+        .arg("--message-format=json-render-diagnostics")
+        .stdout(Stdio::piped());
+    if dashboard::is_active() {
+        cargo_build.stderr(Stdio::piped());
+    }
+    let mut child = cargo_build.spawn().context("failed to spawn cargo build")?;
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(process, stderr, Output::Stderr);
+    }
+    let stdout = child
+        .stdout
+        .take()
+        .context("cargo build stdout is not captured")?;
+    let executable = built_executable(BufReader::new(stdout));
+    let status = child.wait().context("failed to wait for cargo build")?;
+    let result = if status.success() {
+        executable.context("cargo build didn't report an executable")
+    } else {
+        Err(anyhow::anyhow!("cargo build exited with {status}"))
+    };
+    dashboard::report(
+        process,
+        Update::Built(
+            result
+                .as_ref()
+                .map(|_| ())
+                .map_err(|err| format!("{err:#}")),
+        ),
+    );
+    result
+}
+
+#[derive(Clone, Copy)]
+pub(super) enum Output {
+    Stdout,
+    Stderr,
+}
+
+/// Copies the lines of a child's output to the dashboard, or prints them
+/// prefixed with the name of `OoP` modules. Structured log lines are
+/// pretty-printed and filtered by [`logs::render`].
+pub(super) fn forward_lines(
+    process: &str,
+    output: impl Read + Send + 'static,
+    to: Output,
+) -> JoinHandle<()> {
+    let process = process.to_owned();
+    std::thread::spawn(move || {
+        let module = (process != dashboard::SERVER).then_some(process.as_str());
+        let color = !dashboard::is_active()
+            && std::env::var_os("NO_COLOR").is_none()
+            && match to {
+                Output::Stdout => std::io::stdout().is_terminal(),
+                Output::Stderr => std::io::stderr().is_terminal(),
+            };
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            let Some(line) = logs::render(line, module, color) else {
+                continue;
+            };
+            if dashboard::report(&process, Update::Log(line.clone())) {
+                continue;
+            }
+            let line = match module {
+                Some(module) => format!("[{module}] {line}"),
+                None => line,
+            };
+            match to {
+                Output::Stdout => println!("{line}"),
+                Output::Stderr => eprintln!("{line}"),
+            }
+        }
+    })
+}
+
+/// Picks the executable of the last binary artifact from cargo's JSON messages.
+fn built_executable(messages: impl BufRead) -> Option<PathBuf> {
+    messages
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(&line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .filter_map(|message| message["executable"].as_str().map(PathBuf::from))
+        .last()
+}
+
+/// Spawns `cmd` for `process` as the leader of a new process group, stopped by
+/// [`stop`] or on Ctrl+C once [`install_interrupt_handler`] ran. On the
+/// dashboard, its output goes to the process's log pane.
+pub(super) fn spawn_in_group(
+    process: &str,
+    cmd: &mut Command,
+    executable: &Path,
+) -> anyhow::Result<Child> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(cmd, 0);
+    if dashboard::is_active() {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("failed to start {}", executable.display()))?;
+    if let Ok(mut groups) = GROUPS.lock() {
+        groups.insert(child.id());
+    }
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(process, stdout, Output::Stdout);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(process, stderr, Output::Stderr);
+    }
+    dashboard::report(process, Update::Running);
+    Ok(child)
+}
+
+/// Gracefully stops a process started by [`spawn_in_group`] and its group.
+pub(super) fn stop(mut child: Child) {
+    if let Ok(mut groups) = GROUPS.lock() {
+        groups.remove(&child.id());
+    }
+    terminate_group(child.id(), grace_period());
+    // Already reaped by `terminate_group` on unix.
+    let _ = child.wait();
+}
+
+fn grace_period() -> Duration {
+    Duration::from_secs(GRACE_PERIOD.load(Ordering::Relaxed))
+}
+
+/// Processes run in their own process group, so the terminal's Ctrl+C doesn't
+/// reach them: stop them gracefully before exiting.
+pub(super) fn install_interrupt_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let installed = ctrlc::set_handler(|| shutdown(130));
+        if let Err(err) = installed {
+            dashboard::message(
+                dashboard::SERVER,
+                &format!("failed to install the Ctrl+C handler: {err}"),
+            );
+        }
+    });
+}
+
+/// Gracefully stops every running process group, then exits with `code`.
+pub(super) fn shutdown(code: i32) -> ! {
+    let groups = GROUPS
+        .lock()
+        .map(|mut groups| std::mem::take(&mut *groups))
+        .unwrap_or_default();
+    std::thread::scope(|scope| {
+        for pid in groups {
+            scope.spawn(move || terminate_group(pid, grace_period()));
+        }
+    });
+    std::process::exit(code);
+}
+
+/// Sends SIGTERM to the process group led by `pid` and waits up to `grace`
+/// for every process in it to exit, then SIGKILLs whatever is left.
+#[cfg(unix)]
+fn terminate_group(pid: u32, grace: Duration) {
+    use nix::errno::Errno;
+    use nix::sys::signal::{Signal, killpg};
+    use nix::sys::wait::{WaitPidFlag, waitpid};
+    use nix::unistd::Pid;
+    use std::time::Instant;
+
+    let Ok(raw) = i32::try_from(pid) else {
+        return;
+    };
+    let group = Pid::from_raw(raw);
+    if killpg(group, Signal::SIGTERM).is_ok() {
+        let deadline = Instant::now() + grace;
+        loop {
+            // Reap the leader, since a zombie still counts as a group member.
+            let _ = waitpid(group, Some(WaitPidFlag::WNOHANG));
+            if killpg(group, None) == Err(Errno::ESRCH) {
+                return;
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        dashboard::message(
+            dashboard::SERVER,
+            &format!(
+                "process {pid} didn't stop within {}s, killing it",
+                grace.as_secs()
+            ),
+        );
+    }
+    let _ = killpg(group, Signal::SIGKILL);
+    let _ = waitpid(group, None);
+}
+
+#[cfg(not(unix))]
+fn terminate_group(pid: u32, _grace: Duration) {
+    // No process groups or SIGTERM: kill the whole process tree.
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::built_executable;
+    #[cfg(unix)]
+    use super::terminate_group;
+    use std::path::PathBuf;
+    #[cfg(unix)]
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn finds_the_built_executable_in_cargo_messages() {
+        let messages = r#"{"reason":"compiler-artifact","target":{"kind":["lib"]},"executable":null}
+{"reason":"build-script-executed","package_id":"demo"}
+{"reason":"compiler-artifact","target":{"kind":["bin"]},"executable":"/work/target/debug/demo"}
+{"reason":"build-finished","success":true}
+"#;
+
+        assert_eq!(
+            built_executable(messages.as_bytes()),
+            Some(PathBuf::from("/work/target/debug/demo"))
+        );
+        assert_eq!(built_executable(&b"not json\n"[..]), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn stops_the_whole_process_group() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::process::CommandExt;
+        use std::process::{Command, Stdio};
+
+        // The shell and its `sleep` both ignore SIGTERM, so only SIGKILL stops them.
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 30 & echo $!; wait"])
+            .process_group(0)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("spawn sh");
+        let mut grandchild = String::new();
+        BufReader::new(child.stdout.take().expect("piped stdout"))
+            .read_line(&mut grandchild)
+            .expect("read sleep pid");
+
+        let started = Instant::now();
+        terminate_group(child.id(), Duration::from_millis(200));
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let _ = child.wait();
+        // Dead, possibly waiting to be reaped by init.
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", grandchild.trim()));
+        assert!(stat.map_or(true, |stat| stat.contains(") Z ")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn graceful_shutdown_doesnt_wait_for_the_grace_period() {
+        use std::os::unix::process::CommandExt;
+        use std::process::Command;
+
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30"])
+            .process_group(0)
+            .spawn()
+            .expect("spawn sh");
+        std::thread::sleep(Duration::from_millis(100));
+
+        let started = Instant::now();
+        terminate_group(child.id(), Duration::from_secs(30));
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let _ = child.wait();
+    }
+}
@@ -1,21 +1,44 @@
-use crate::{app_config, common};
+use super::dashboard::{self, SERVER, Update};
+use super::oop::{self, OopModule, Supervisor};
+use super::process::{self, Output};
+use super::watch_filter::WatchFilter;
+use crate::common;
+use crate::common::ConfigLayers;
+use crate::config::env::EnvVars;
 use anyhow::{Context, bail};
+use module_parser::{CargoTomlDependencies, get_module_name_from_crate};
 use notify::{RecursiveMode, Watcher};
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command, Stdio};
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc;
 use std::time::Duration;
 
 pub(super) enum RunSignal {
+    /// Rebuild, then restart on success.
     Rerun,
+    /// Restart the last build without rebuilding.
+    Restart,
     Stop,
 }
 
+/// Watch mode settings.
+pub(super) struct WatchOptions {
+    /// Quiet period that ends a burst of file events.
+    pub(super) debounce: Duration,
+    /// Globs of paths whose changes don't trigger a restart.
+    pub(super) ignore: Vec<String>,
+    /// Directories watched on top of the path dependencies.
+    pub(super) paths: Vec<PathBuf>,
+}
+
 pub(super) struct RunLoop {
-    config_path: PathBuf,
+    layers: ConfigLayers,
     project_name: String,
+    vars: Option<EnvVars>,
+    /// Build and supervise the workspace's `OoP` modules.
+    oop: bool,
 }
 
 pub(super) static OTEL: AtomicBool = AtomicBool::new(false);
@@ -23,37 +46,94 @@ pub(super) static FIPS: AtomicBool = AtomicBool::new(false);
 pub(super) static RELEASE: AtomicBool = AtomicBool::new(false);
 
 impl RunLoop {
-    pub(super) const fn new(config_path: PathBuf, project_name: String) -> Self {
+    pub(super) const fn new(
+        layers: ConfigLayers,
+        project_name: String,
+        vars: Option<EnvVars>,
+        oop: bool,
+    ) -> Self {
         Self {
-            config_path,
+            layers,
             project_name,
+            vars,
+            oop,
+        }
+    }
+
+    /// Merges (and expands) the config layers, returning the server config path
+    /// and the `OoP` modules the CLI starts instead of the server.
+    fn materialize_config(&self) -> anyhow::Result<(PathBuf, Vec<OopModule>)> {
+        if !self.oop {
+            let path = self
+                .layers
+                .materialize(&self.project_name, self.vars.as_ref())?;
+            return Ok((path, Vec::new()));
         }
+        let members = get_module_name_from_crate()?;
+        self.layers
+            .materialize_with(&self.project_name, self.vars.as_ref(), |config| {
+                oop::detach(config, &members)
+            })
     }
 
-    pub(super) fn run(&self, watch: bool) -> anyhow::Result<RunSignal> {
+    fn load_config(&self) -> anyhow::Result<(CargoTomlDependencies, Vec<OopModule>)> {
+        let (path, oop_modules) = self.materialize_config()?;
+        let dependencies = common::get_config(&path)?.create_dependencies(self.layers.last())?;
+        Ok((dependencies, oop_modules))
+    }
+
+    pub(super) fn run(&self, watch: Option<&WatchOptions>) -> anyhow::Result<RunSignal> {
         let workspace_path = common::workspace_root()?;
-        let dependencies = common::get_config(&self.config_path)?.create_dependencies()?;
+        let (config_path, mut oop_modules) = self.materialize_config()?;
+        let dependencies =
+            common::get_config(&config_path)?.create_dependencies(self.layers.last())?;
         common::generate_server_structure(&self.project_name, &dependencies)?;
 
         let cargo_dir = common::generated_project_dir(&self.project_name)?;
 
-        if !watch {
-            let status = cargo_run(&cargo_dir, &self.config_path)?
-                .status()
-                .context("failed to run cargo")?;
+        let Some(options) = watch else {
+            let supervisor = supervise(&oop_modules, &workspace_path);
+            let status = cargo_run(&cargo_dir, &config_path).and_then(|mut cmd| {
+                let mut child = cmd
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .context("failed to run cargo")?;
+                let logs = child
+                    .stdout
+                    .take()
+                    .map(|stdout| process::forward_lines(SERVER, stdout, Output::Stdout));
+                let status = child.wait().context("failed to wait for cargo");
+                if let Some(logs) = logs {
+                    _ = logs.join();
+                }
+                status
+            });
+            drop(supervisor);
+            let status = status?;
             if !status.success() {
                 bail!("cargo run exited with {status}");
             }
             return Ok(RunSignal::Stop);
-        }
+        };
 
         // -- watch mode --
 
+        let extra_paths = options
+            .paths
+            .iter()
+            .map(|path| {
+                path.canonicalize()
+                    .with_context(|| format!("can't watch {}", path.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        process::install_interrupt_handler();
+
         let (signal_tx, signal_rx) = mpsc::channel::<RunSignal>();
+        dashboard::register(SERVER, signal_tx.clone());
 
         // Spawn cargo-run loop in a dedicated thread
         let cargo_dir_clone = cargo_dir;
-        let config_path = self.config_path.clone();
         let runner_handle = std::thread::spawn(move || {
             cargo_run_loop(&cargo_dir_clone, &config_path, &signal_rx);
         });
@@ -67,65 +147,87 @@ impl RunLoop {
         // (write to temporary file, then rename), the rename event is reported at the directory level,
         // not the file level. File-level watches can therefore miss these events and fail to detect config changes.
         // Watching the parent directory is the documented best practice.
-        let config_parent = self
-            .config_path
-            .parent()
-            .context("config path has no parent directory")?;
+        let mut config_parents = HashSet::new();
+        for layer in self.layers.paths() {
+            config_parents.insert(
+                layer
+                    .parent()
+                    .context("config path has no parent directory")?
+                    .to_path_buf(),
+            );
+        }
         let workspace_manifest = workspace_path.join("Cargo.toml");
-        watcher
-            .watch(config_parent, RecursiveMode::NonRecursive)
-            .context("failed to watch config directory")?;
-        if config_parent != workspace_path.as_path() {
+        for config_parent in &config_parents {
+            watcher
+                .watch(config_parent, RecursiveMode::NonRecursive)
+                .context("failed to watch config directory")?;
+        }
+        if !config_parents.contains(&workspace_path) {
             watcher
                 .watch(&workspace_path, RecursiveMode::NonRecursive)
                 .context("failed to watch workspace directory")?;
         }
 
-        // Watch dependency paths that have `path` set
+        for path in &extra_paths {
+            watcher
+                .watch(path, RecursiveMode::Recursive)
+                .with_context(|| format!("failed to watch {}", path.display()))?;
+        }
+
+        // Watch dependency paths that have `path` set, and `OoP` module crates
         let mut watched_paths =
-            watch_dependency_paths(&dependencies, &mut watcher, &workspace_path);
+            watch_dependency_paths(&dependencies, &oop_modules, &mut watcher, &workspace_path);
         let mut current_deps = dependencies;
+        let mut filter = watch_filter(options, &workspace_path, &watched_paths, &extra_paths)?;
+        let mut supervisor = supervise(&oop_modules, &workspace_path);
 
-        // Event loop - runs until the watcher channel closes
-        while let Ok(res_event) = fs_rx.recv() {
-            let event = match res_event {
-                Ok(event) => event,
-                Err(err) => {
-                    eprintln!("file watcher error: {err}");
-                    continue;
+        // Event loop - runs until the watcher channel closes. Bursts of events,
+        // such as a save touching several files, are handled as one change.
+        while let Ok(first) = fs_rx.recv() {
+            let mut events = Vec::new();
+            for res_event in debounced_batch(&fs_rx, first, options.debounce) {
+                match res_event {
+                    Ok(event) => events.push(event),
+                    Err(err) => dashboard::message(SERVER, &format!("file watcher error: {err}")),
                 }
-            };
-            let is_config_change = event.paths.contains(&self.config_path)
-                && matches!(
-                    event.kind,
-                    notify::EventKind::Modify(_)
-                        | notify::EventKind::Create(_)
-                        | notify::EventKind::Remove(_)
-                );
-            let is_workspace_manifest_change = event.paths.contains(&workspace_manifest)
-                && matches!(
-                    event.kind,
-                    notify::EventKind::Modify(_)
-                        | notify::EventKind::Create(_)
-                        | notify::EventKind::Remove(_)
-                );
+            }
+            if events.is_empty() {
+                continue;
+            }
+            let is_config_change = events.iter().any(|event| {
+                self.layers
+                    .paths()
+                    .iter()
+                    .any(|layer| changes(event, layer))
+            });
+            let is_workspace_manifest_change = events
+                .iter()
+                .any(|event| changes(event, &workspace_manifest));
 
             if is_config_change || is_workspace_manifest_change {
-                match common::get_config(&self.config_path)
-                    .and_then(app_config::AppConfig::create_dependencies)
-                {
-                    Ok(new_deps) => {
-                        if new_deps != current_deps {
+                match self.load_config() {
+                    Ok((new_deps, new_oop_modules)) => {
+                        if new_deps != current_deps || new_oop_modules != oop_modules {
                             if let Err(e) =
                                 common::generate_server_structure(&self.project_name, &new_deps)
                             {
-                                eprintln!("failed to regenerate server structure: {e}");
+                                dashboard::message(
+                                    SERVER,
+                                    &format!("failed to regenerate server structure: {e}"),
+                                );
                             } else {
                                 // Reconcile watched dependency paths
-                                let new_watched = collect_dep_paths(&new_deps, &workspace_path);
+                                let new_watched = collect_watch_paths(
+                                    &new_deps,
+                                    &new_oop_modules,
+                                    &workspace_path,
+                                );
                                 for old in watched_paths.difference(&new_watched) {
                                     if let Err(err) = watcher.unwatch(old) {
-                                        eprintln!("failed to unwatch {}: {err}", old.display());
+                                        dashboard::message(
+                                            SERVER,
+                                            &format!("failed to unwatch {}: {err}", old.display()),
+                                        );
                                         _ = signal_tx.send(RunSignal::Stop);
                                         runner_handle.join().map_err(|e| {
                                             anyhow::anyhow!("runner thread panicked: {e:?}")
@@ -136,7 +238,10 @@ impl RunLoop {
                                 for new_p in new_watched.difference(&watched_paths) {
                                     if let Err(err) = watcher.watch(new_p, RecursiveMode::Recursive)
                                     {
-                                        eprintln!("failed to watch {}: {err}", new_p.display());
+                                        dashboard::message(
+                                            SERVER,
+                                            &format!("failed to watch {}: {err}", new_p.display()),
+                                        );
                                         _ = signal_tx.send(RunSignal::Stop);
                                         runner_handle.join().map_err(|e| {
                                             anyhow::anyhow!("runner thread panicked: {e:?}")
@@ -146,15 +251,47 @@ impl RunLoop {
                                 }
                                 watched_paths = new_watched;
                                 current_deps = new_deps;
+                                if new_oop_modules != oop_modules {
+                                    drop(supervisor.take());
+                                    supervisor = supervise(&new_oop_modules, &workspace_path);
+                                    oop_modules = new_oop_modules;
+                                }
+                                match watch_filter(
+                                    options,
+                                    &workspace_path,
+                                    &watched_paths,
+                                    &extra_paths,
+                                ) {
+                                    Ok(new_filter) => filter = new_filter,
+                                    Err(e) => dashboard::message(
+                                        SERVER,
+                                        &format!("failed to update watch filters: {e:#}"),
+                                    ),
+                                }
                             }
                         }
                         _ = signal_tx.send(RunSignal::Rerun);
                     }
-                    Err(e) => eprintln!("failed to reload config: {e}"),
+                    Err(e) => dashboard::message(SERVER, &format!("failed to reload config: {e}")),
                 }
             } else {
-                // A watched dependency path changed
-                _ = signal_tx.send(RunSignal::Rerun);
+                let changed: Vec<&Path> = events
+                    .iter()
+                    .flat_map(|event| &event.paths)
+                    .map(PathBuf::as_path)
+                    .filter(|path| !filter.is_ignored(path))
+                    .collect();
+                if let Some(supervisor) = &supervisor {
+                    supervisor.restart_changed(&changed);
+                }
+                if changed.iter().any(|path| {
+                    !oop_modules
+                        .iter()
+                        .any(|module| module.dir().is_some_and(|dir| path.starts_with(dir)))
+                }) {
+                    // A watched dependency or `--watch-path` changed
+                    _ = signal_tx.send(RunSignal::Rerun);
+                }
             }
         }
 
@@ -175,95 +312,211 @@ fn cargo_run(path: &Path, config_path: &Path) -> anyhow::Result<Command> {
     common::cargo_command("run", path, config_path, otel, fips, release)
 }
 
-fn cargo_run_loop(cargo_dir: &Path, config_path: &Path, signal_rx: &mpsc::Receiver<RunSignal>) {
-    'outer: loop {
-        let mut child = match cargo_run(cargo_dir, config_path)
-            .and_then(|mut cmd| cmd.spawn().context("failed to spawn cargo run"))
-        {
-            Ok(child) => child,
-            Err(e) => {
-                eprintln!("failed to spawn cargo run: {e}");
-                match signal_rx.recv() {
-                    Ok(RunSignal::Rerun) => continue 'outer,
-                    _ => return,
-                }
-            }
-        };
+/// Builds the generated server and returns its executable.
+fn cargo_build(path: &Path, config_path: &Path) -> anyhow::Result<PathBuf> {
+    let otel = OTEL.load(std::sync::atomic::Ordering::Relaxed);
+    let fips = FIPS.load(std::sync::atomic::Ordering::Relaxed);
+    let release = RELEASE.load(std::sync::atomic::Ordering::Relaxed);
+    process::build_executable(
+        SERVER,
+        common::cargo_command("build", path, config_path, otel, fips, release)?,
+    )
+}
 
-        let rerun = loop {
-            match child.try_wait() {
-                Ok(Some(_)) => break false,
-                Ok(None) => {}
-                Err(e) => {
-                    eprintln!("error checking child status: {e}");
-                    break false;
-                }
-            }
+/// Starts the built server the way `cargo run` would.
+pub(super) fn spawn_server(
+    executable: &Path,
+    cargo_dir: &Path,
+    config_path: &Path,
+) -> anyhow::Result<Child> {
+    let mut cmd = Command::new(executable);
+    // Captured so that structured logs are pretty-printed.
+    cmd.env(common::CONFIG_PATH_ENV_VAR, config_path)
+        .current_dir(cargo_dir)
+        .stdout(Stdio::piped());
+    process::spawn_in_group(SERVER, &mut cmd, executable)
+}
 
-            match signal_rx.try_recv() {
-                Ok(RunSignal::Rerun) => {
-                    // Drain extra reruns; honor a queued Stop.
-                    let mut stop = false;
-                    loop {
-                        match signal_rx.try_recv() {
-                            Ok(RunSignal::Rerun) => {}
-                            Ok(RunSignal::Stop) | Err(mpsc::TryRecvError::Disconnected) => {
-                                stop = true;
-                                break;
-                            }
-                            Err(mpsc::TryRecvError::Empty) => break,
-                        }
-                    }
-                    let _ = child.kill();
-                    let _ = child.wait();
-                    if stop {
-                        return;
-                    }
-                    break true;
+fn stop_server(server: &mut Option<Child>) {
+    if let Some(child) = server.take() {
+        process::stop(child);
+    }
+}
+
+/// Rebuilds on every [`RunSignal::Rerun`] while the current server keeps
+/// serving, and only swaps it for the new build once that build succeeded.
+/// [`RunSignal::Restart`] starts the last build again.
+fn cargo_run_loop(cargo_dir: &Path, config_path: &Path, signal_rx: &mpsc::Receiver<RunSignal>) {
+    let mut server: Option<Child> = None;
+    let mut executable = None;
+    let mut signal = RunSignal::Rerun;
+    loop {
+        match signal {
+            RunSignal::Rerun => match cargo_build(cargo_dir, config_path) {
+                Ok(built) => {
+                    stop_server(&mut server);
+                    start_server(&mut server, &built, cargo_dir, config_path);
+                    executable = Some(built);
                 }
-                Ok(RunSignal::Stop) | Err(mpsc::TryRecvError::Disconnected) => {
-                    let _ = child.kill();
-                    let _ = child.wait();
-                    return;
+                Err(e) if server.is_some() => dashboard::message(
+                    SERVER,
+                    &format!("{e:#}, the running server is kept until the next successful build"),
+                ),
+                Err(e) => dashboard::message(SERVER, &format!("{e:#}")),
+            },
+            RunSignal::Restart => {
+                stop_server(&mut server);
+                if let Some(built) = &executable {
+                    start_server(&mut server, built, cargo_dir, config_path);
                 }
-                Err(mpsc::TryRecvError::Empty) => {}
             }
+            RunSignal::Stop => {
+                stop_server(&mut server);
+                return;
+            }
+        }
+        signal = wait_for_signal(&mut server, signal_rx);
+    }
+}
 
-            std::thread::sleep(Duration::from_millis(100));
-        };
+fn start_server(
+    server: &mut Option<Child>,
+    executable: &Path,
+    cargo_dir: &Path,
+    config_path: &Path,
+) {
+    match spawn_server(executable, cargo_dir, config_path) {
+        Ok(child) => *server = Some(child),
+        Err(e) => {
+            dashboard::report(SERVER, Update::Exited(format!("{e:#}")));
+            dashboard::message(SERVER, &format!("{e:#}"));
+        }
+    }
+}
 
-        if rerun {
-            continue 'outer;
+/// Waits for the next signal, reaping the server if it exits on its own.
+/// Queued signals are merged: a `Stop` wins, then a `Rerun`.
+fn wait_for_signal(server: &mut Option<Child>, signal_rx: &mpsc::Receiver<RunSignal>) -> RunSignal {
+    loop {
+        if let Some(child) = server {
+            let exited = match child.try_wait() {
+                Ok(Some(status)) => Some(format!("exited with {status}")),
+                Ok(None) => None,
+                Err(e) => Some(format!("can't check its status: {e}")),
+            };
+            if let Some(reason) = exited {
+                dashboard::report(SERVER, Update::Exited(reason.clone()));
+                dashboard::message(SERVER, &format!("server {reason}, waiting for changes"));
+                stop_server(server);
+            }
         }
 
-        // Child exited on its own, wait for a signal before restarting
-        match signal_rx.recv() {
-            Ok(RunSignal::Rerun) => {}
-            _ => return,
+        match signal_rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(mut signal) => loop {
+                match signal_rx.try_recv() {
+                    Ok(RunSignal::Stop) | Err(mpsc::TryRecvError::Disconnected) => {
+                        return RunSignal::Stop;
+                    }
+                    Ok(RunSignal::Rerun) => signal = RunSignal::Rerun,
+                    Ok(RunSignal::Restart) => {}
+                    Err(mpsc::TryRecvError::Empty) => return signal,
+                }
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => return RunSignal::Stop,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
         }
     }
 }
 
-fn collect_dep_paths(
-    deps: &module_parser::CargoTomlDependencies,
+/// Whether `event` creates, modifies or removes `path`.
+fn changes(event: &notify::Event, path: &Path) -> bool {
+    event.paths.iter().any(|changed| changed == path)
+        && matches!(
+            event.kind,
+            notify::EventKind::Modify(_)
+                | notify::EventKind::Create(_)
+                | notify::EventKind::Remove(_)
+        )
+}
+
+/// Collects `first` and every event that follows within `debounce` of the
+/// previous one.
+fn debounced_batch<T>(rx: &mpsc::Receiver<T>, first: T, debounce: Duration) -> Vec<T> {
+    let mut batch = vec![first];
+    while let Ok(event) = rx.recv_timeout(debounce) {
+        batch.push(event);
+    }
+    batch
+}
+
+fn watch_filter(
+    options: &WatchOptions,
+    workspace_path: &Path,
+    dep_paths: &HashSet<PathBuf>,
+    extra_paths: &[PathBuf],
+) -> anyhow::Result<WatchFilter> {
+    WatchFilter::new(
+        workspace_path,
+        &options.ignore,
+        dep_paths.iter().chain(extra_paths).map(PathBuf::as_path),
+    )
+}
+
+fn collect_watch_paths(
+    deps: &CargoTomlDependencies,
+    oop_modules: &[OopModule],
     base_path: &Path,
 ) -> HashSet<PathBuf> {
     deps.values()
         .filter_map(|d| d.path.as_ref())
         .map(|p| base_path.join(p))
+        .chain(
+            oop_modules
+                .iter()
+                .filter_map(|m| m.dir().map(Path::to_path_buf)),
+        )
         .collect()
 }
 
 fn watch_dependency_paths(
-    deps: &module_parser::CargoTomlDependencies,
+    deps: &CargoTomlDependencies,
+    oop_modules: &[OopModule],
     watcher: &mut impl Watcher,
     base_path: &Path,
 ) -> HashSet<PathBuf> {
-    let paths = collect_dep_paths(deps, base_path);
+    let paths = collect_watch_paths(deps, oop_modules, base_path);
     for p in &paths {
         if let Err(e) = watcher.watch(p, RecursiveMode::Recursive) {
-            eprintln!("failed to watch {}: {e}", p.display());
+            dashboard::message(SERVER, &format!("failed to watch {}: {e}", p.display()));
         }
     }
     paths
 }
+
+/// Starts supervising `modules`, if any.
+fn supervise(modules: &[OopModule], workspace_path: &Path) -> Option<Supervisor> {
+    if modules.is_empty() {
+        return None;
+    }
+    process::install_interrupt_handler();
+    Some(Supervisor::start(modules.to_vec(), workspace_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::debounced_batch;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn bursts_of_events_are_batched() {
+        let (tx, rx) = mpsc::channel();
+        for event in 2..=4 {
+            tx.send(event).expect("send");
+        }
+
+        let batch = debounced_batch(&rx, 1, Duration::from_millis(20));
+
+        assert_eq!(batch, vec![1, 2, 3, 4]);
+    }
+}
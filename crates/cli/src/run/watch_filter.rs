@@ -0,0 +1,150 @@
+//! Decides which file events are worth a restart in watch mode.
+//!
+//! A path is ignored when it matches one of the built-in or `--ignore` globs,
+//! or a `.gitignore` found in the workspace or a watched directory.
+
+use super::dashboard::{self, SERVER};
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use ignore::gitignore::Gitignore;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Build output, VCS metadata and editor swap and backup files.
+const DEFAULT_IGNORES: &[&str] = &[
+    "**/.git",
+    "**/.git/**",
+    "**/target",
+    "**/target/**",
+    "**/.cyberfabric",
+    "**/.cyberfabric/**",
+    "**/*.swp",
+    "**/*.swx",
+    "**/*~",
+    "**/.#*",
+];
+
+pub(super) struct WatchFilter {
+    root: PathBuf,
+    globs: GlobSet,
+    gitignores: Vec<Gitignore>,
+}
+
+impl WatchFilter {
+    /// Globs are matched against both the absolute path and the path relative
+    /// to `root`. `.gitignore` files are collected from `root` and `watched`.
+    pub(super) fn new<'a>(
+        root: &Path,
+        patterns: &[String],
+        watched: impl IntoIterator<Item = &'a Path>,
+    ) -> anyhow::Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in DEFAULT_IGNORES
+            .iter()
+            .copied()
+            .chain(patterns.iter().map(String::as_str))
+        {
+            builder.add(
+                Glob::new(pattern).with_context(|| format!("invalid ignore glob '{pattern}'"))?,
+            );
+        }
+        let globs = builder.build().context("failed to build ignore globs")?;
+
+        let mut files = BTreeSet::new();
+        files.extend(gitignore_files(root));
+        for dir in watched {
+            files.extend(gitignore_files(dir));
+        }
+        let gitignores = files
+            .into_iter()
+            .filter_map(|file| {
+                let (gitignore, err) = Gitignore::new(&file);
+                if let Some(err) = err {
+                    dashboard::message(
+                        SERVER,
+                        &format!("warning: can't fully read {}: {err}", file.display()),
+                    );
+                }
+                (!gitignore.is_empty()).then_some(gitignore)
+            })
+            .collect();
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            globs,
+            gitignores,
+        })
+    }
+
+    pub(super) fn is_ignored(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if self.globs.is_match(path) || self.globs.is_match(relative) {
+            return true;
+        }
+        let is_dir = path.is_dir();
+        self.gitignores.iter().any(|gitignore| {
+            path.starts_with(gitignore.path())
+                && gitignore
+                    .matched_path_or_any_parents(path, is_dir)
+                    .is_ignore()
+        })
+    }
+}
+
+/// `.gitignore` files under `dir`, skipping directories they already ignore.
+fn gitignore_files(dir: &Path) -> Vec<PathBuf> {
+    WalkBuilder::new(dir)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name() == ".gitignore")
+        .map(ignore::DirEntry::into_path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WatchFilter;
+    use std::fs;
+
+    #[test]
+    fn honours_gitignore_files_and_ignore_globs() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        let root = temp_dir.path();
+        let module = root.join("modules/billing");
+        let assets = temp_dir.path().join("assets");
+        for dir in [module.join("src"), module.join("generated"), assets.clone()] {
+            fs::create_dir_all(dir).expect("create dir");
+        }
+        fs::write(root.join(".gitignore"), "*.log\n").expect("write");
+        fs::write(module.join(".gitignore"), "generated/\n").expect("write");
+
+        let filter = WatchFilter::new(root, &["**/*.tmp".to_owned()], [module.as_path()])
+            .expect("filter builds");
+
+        for ignored in [
+            root.join("target/debug/server"),
+            root.join(".cyberfabric"),
+            root.join("server.log"),
+            module.join("generated/schema.rs"),
+            module.join("src/.lib.rs.swp"),
+            assets.join("logo.png.tmp"),
+        ] {
+            assert!(
+                filter.is_ignored(&ignored),
+                "{} is watched",
+                ignored.display()
+            );
+        }
+        for watched in [module.join("src/lib.rs"), assets.join("logo.png")] {
+            assert!(
+                !filter.is_ignored(&watched),
+                "{} is ignored",
+                watched.display()
+            );
+        }
+    }
+}
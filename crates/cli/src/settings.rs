@@ -0,0 +1,442 @@
+use crate::deploy::BuildTool;
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default repository holding the `CyberFabric` project and module templates.
+pub const DEFAULT_TEMPLATE_GIT: &str = "https://github.com/cyberfabric/cf-template-rust";
+pub const DEFAULT_TEMPLATE_BRANCH: &str = "main";
+pub const DEFAULT_INIT_SUBFOLDER: &str = "Init";
+pub const DEFAULT_MODULES_SUBFOLDER: &str = "Modules";
+/// Name under which the default template repository is listed.
+pub const DEFAULT_TEMPLATE_SOURCE: &str = "cyberfabric";
+/// Dedicated settings file, next to the workspace `Cargo.toml`.
+pub const SETTINGS_FILE: &str = "cyberfabric.toml";
+
+/// Project-level CLI settings, read from `[workspace.metadata.cyberfabric]` in
+/// the workspace `Cargo.toml` and from `cyberfabric.toml` so every developer of
+/// the repo shares them. Keys of `cyberfabric.toml` win over the manifest ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProjectSettings {
+    /// Default config file for commands taking `-c/--config`.
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    #[serde(default)]
+    pub init: TemplateDefaults,
+    #[serde(default)]
+    pub r#mod: TemplateDefaults,
+    #[serde(default)]
+    pub build: BuildDefaults,
+    #[serde(default)]
+    pub deploy: DeployDefaults,
+    /// Extra module template sources, searched before the default one.
+    #[serde(default)]
+    pub template_sources: Vec<TemplateSource>,
+}
+
+/// Defaults for `--git`, `--branch` and `--subfolder` of `init` and `mod add`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TemplateDefaults {
+    #[serde(default)]
+    pub git: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub subfolder: Option<String>,
+}
+
+/// Defaults for `build` and `run`, each overridden by its flag or `--no-` flag.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BuildDefaults {
+    #[serde(default)]
+    pub otel: Option<bool>,
+    #[serde(default)]
+    pub fips: Option<bool>,
+    #[serde(default)]
+    pub release: Option<bool>,
+}
+
+/// Defaults for `deploy`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DeployDefaults {
+    /// Image tag; `{name}` and `{version}` are replaced by the artifact name
+    /// and the workspace package version.
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub dockerfile: Option<PathBuf>,
+    /// Dockerfile ARGs, overridden key by key by `--args`.
+    #[serde(default)]
+    pub args: BTreeMap<String, String>,
+    /// Target platforms, replaced by `--platform`.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// Tool that builds the image, replaced by `--builder`.
+    #[serde(default)]
+    pub builder: Option<BuildTool>,
+}
+
+/// A git repository or local directory holding module templates, one per
+/// subfolder of `subfolder`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TemplateSource {
+    pub name: String,
+    #[serde(default)]
+    pub git: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Local template root. Relative paths are resolved from the workspace root.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default = "default_modules_subfolder")]
+    pub subfolder: String,
+}
+
+impl TemplateSource {
+    #[must_use]
+    pub fn default_modules() -> Self {
+        Self {
+            name: DEFAULT_TEMPLATE_SOURCE.to_owned(),
+            git: Some(DEFAULT_TEMPLATE_GIT.to_owned()),
+            branch: Some(DEFAULT_TEMPLATE_BRANCH.to_owned()),
+            path: None,
+            subfolder: DEFAULT_MODULES_SUBFOLDER.to_owned(),
+        }
+    }
+
+    /// The default source with the `[mod]` defaults of the project applied.
+    fn default_modules_with(defaults: &TemplateDefaults) -> Self {
+        let mut source = Self::default_modules();
+        if let Some(git) = &defaults.git {
+            source.git = Some(git.clone());
+            source.branch.clone_from(&defaults.branch);
+        }
+        if let Some(branch) = &defaults.branch {
+            source.branch = Some(branch.clone());
+        }
+        if let Some(subfolder) = &defaults.subfolder {
+            source.subfolder.clone_from(subfolder);
+        }
+        source
+    }
+
+    /// Human readable location, e.g. `https://host/repo#main` or a local path.
+    #[must_use]
+    pub fn location(&self) -> String {
+        match (&self.path, &self.git, &self.branch) {
+            (Some(path), _, _) => path.display().to_string(),
+            (None, Some(git), Some(branch)) => format!("{git}#{branch}"),
+            (None, Some(git), None) => git.clone(),
+            (None, None, _) => "(no location)".to_owned(),
+        }
+    }
+}
+
+fn default_modules_subfolder() -> String {
+    DEFAULT_MODULES_SUBFOLDER.to_owned()
+}
+
+#[derive(Deserialize)]
+struct WorkspaceManifest {
+    workspace: Option<WorkspaceSection>,
+}
+
+#[derive(Deserialize)]
+struct WorkspaceSection {
+    metadata: Option<WorkspaceMetadata>,
+}
+
+#[derive(Deserialize)]
+struct WorkspaceMetadata {
+    cyberfabric: Option<toml::Table>,
+}
+
+impl ProjectSettings {
+    /// Loads the settings of the workspace at `workspace_root`. Missing files
+    /// or tables yield the defaults.
+    pub fn load(workspace_root: &Path) -> anyhow::Result<Self> {
+        let manifest_path = workspace_root.join("Cargo.toml");
+        let manifest = if manifest_path.is_file() {
+            let raw = fs::read_to_string(&manifest_path)
+                .with_context(|| format!("can't read {}", manifest_path.display()))?;
+            Some(parse_metadata(&raw).with_context(|| {
+                format!(
+                    "invalid [workspace.metadata.cyberfabric] in {}",
+                    manifest_path.display()
+                )
+            })?)
+        } else {
+            None
+        };
+
+        let settings_path = workspace_root.join(SETTINGS_FILE);
+        let dedicated = if settings_path.is_file() {
+            let raw = fs::read_to_string(&settings_path)
+                .with_context(|| format!("can't read {}", settings_path.display()))?;
+            Some(
+                toml::from_str::<toml::Table>(&raw)
+                    .with_context(|| format!("invalid {}", settings_path.display()))?,
+            )
+        } else {
+            None
+        };
+
+        let mut settings = Self::from_tables(manifest.flatten(), dedicated)
+            .with_context(|| format!("invalid CLI settings in {}", workspace_root.display()))?;
+        settings.resolve_paths(workspace_root);
+        Ok(settings)
+    }
+
+    #[cfg(test)]
+    fn parse_manifest(raw: &str) -> anyhow::Result<Self> {
+        Self::from_tables(parse_metadata(raw)?, None)
+    }
+
+    /// Merges the dedicated file over the manifest metadata, key by key.
+    fn from_tables(
+        manifest: Option<toml::Table>,
+        dedicated: Option<toml::Table>,
+    ) -> anyhow::Result<Self> {
+        let mut merged = manifest.unwrap_or_default();
+        if let Some(dedicated) = dedicated {
+            merge_tables(&mut merged, dedicated);
+        }
+        Ok(toml::Value::Table(merged).try_into()?)
+    }
+
+    fn resolve_paths(&mut self, workspace_root: &Path) {
+        let paths = self
+            .template_sources
+            .iter_mut()
+            .filter_map(|source| source.path.as_mut())
+            .chain(self.config.as_mut())
+            .chain(self.deploy.dockerfile.as_mut());
+        for path in paths {
+            if path.is_relative() {
+                *path = workspace_root.join(&*path);
+            }
+        }
+    }
+
+    /// Project template sources followed by the default one.
+    #[must_use]
+    pub fn module_template_sources(&self) -> Vec<TemplateSource> {
+        let mut sources = self.template_sources.clone();
+        if !sources
+            .iter()
+            .any(|source| source.name == DEFAULT_TEMPLATE_SOURCE)
+        {
+            sources.push(TemplateSource::default_modules_with(&self.r#mod));
+        }
+        sources
+    }
+
+    /// Expands the `{name}` and `{version}` placeholders of the deploy tag.
+    #[allow(clippy::literal_string_with_formatting_args)]
+    pub fn deploy_tag(
+        &self,
+        name: &str,
+        version: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(pattern) = &self.deploy.tag else {
+            return Ok(None);
+        };
+        let mut tag = pattern.replace("{name}", name);
+        if tag.contains("{version}") {
+            tag = tag.replace("{version}", &version()?);
+        }
+        if let Some(start) = tag.find('{') {
+            bail!(
+                "unknown placeholder in deploy tag '{pattern}' at '{}'; use {{name}} or {{version}}",
+                &tag[start..]
+            );
+        }
+        Ok(Some(tag))
+    }
+}
+
+fn parse_metadata(raw: &str) -> anyhow::Result<Option<toml::Table>> {
+    let manifest: WorkspaceManifest = toml::from_str(raw)?;
+    Ok(manifest
+        .workspace
+        .and_then(|workspace| workspace.metadata)
+        .and_then(|metadata| metadata.cyberfabric))
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_TEMPLATE_SOURCE, ProjectSettings, TemplateSource};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn reads_template_sources_from_workspace_metadata() {
+        let raw = r#"
+            [workspace]
+            members = []
+
+            [[workspace.metadata.cyberfabric.template-sources]]
+            name = "acme"
+            git = "https://example.com/acme/templates"
+            branch = "stable"
+
+            [[workspace.metadata.cyberfabric.template-sources]]
+            name = "local"
+            path = "templates"
+            subfolder = "modules"
+        "#;
+
+        let mut settings = ProjectSettings::parse_manifest(raw).expect("settings should parse");
+        settings.resolve_paths(Path::new("/ws"));
+        let sources = settings.module_template_sources();
+
+        assert_eq!(
+            sources
+                .iter()
+                .map(|source| (
+                    source.name.as_str(),
+                    source.location(),
+                    source.subfolder.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "acme",
+                    "https://example.com/acme/templates#stable".to_owned(),
+                    "Modules"
+                ),
+                ("local", "/ws/templates".to_owned(), "modules"),
+                (
+                    DEFAULT_TEMPLATE_SOURCE,
+                    "https://github.com/cyberfabric/cf-template-rust#main".to_owned(),
+                    "Modules"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_metadata_uses_defaults() {
+        let settings = ProjectSettings::parse_manifest("[workspace]\nmembers = []\n")
+            .expect("settings should parse");
+        assert_eq!(
+            settings.module_template_sources(),
+            vec![TemplateSource::default_modules()]
+        );
+    }
+
+    #[test]
+    fn project_can_replace_the_default_source() {
+        let raw = r#"
+            [[workspace.metadata.cyberfabric.template-sources]]
+            name = "cyberfabric"
+            path = "/opt/templates"
+        "#;
+        let settings = ProjectSettings::parse_manifest(raw).expect("settings should parse");
+        let sources = settings.module_template_sources();
+
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].path, Some(PathBuf::from("/opt/templates")));
+    }
+
+    #[test]
+    fn settings_file_overrides_manifest_metadata_key_by_key() {
+        let temp_dir = tempfile::tempdir().expect("temp dir");
+        fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            r#"
+                [workspace.metadata.cyberfabric]
+                config = "config/quickstart.yml"
+
+                [workspace.metadata.cyberfabric.build]
+                otel = true
+
+                [workspace.metadata.cyberfabric.deploy]
+                tag = "acme/app:{version}"
+                args = { RUST_VERSION = "1.92" }
+            "#,
+        )
+        .expect("write manifest");
+        fs::write(
+            temp_dir.path().join("cyberfabric.toml"),
+            r#"
+                [mod]
+                branch = "stable"
+
+                [deploy]
+                dockerfile = "docker/Dockerfile"
+                args = { BUILDER_FLAGS = "--locked" }
+            "#,
+        )
+        .expect("write settings");
+
+        let settings = ProjectSettings::load(temp_dir.path()).expect("settings should load");
+
+        assert_eq!(
+            settings.config,
+            Some(temp_dir.path().join("config/quickstart.yml"))
+        );
+        assert_eq!(settings.build.otel, Some(true));
+        assert_eq!(settings.build.release, None);
+        assert_eq!(settings.deploy.tag.as_deref(), Some("acme/app:{version}"));
+        assert_eq!(
+            settings.deploy.dockerfile,
+            Some(temp_dir.path().join("docker/Dockerfile"))
+        );
+        assert_eq!(
+            settings.deploy.args.keys().collect::<Vec<_>>(),
+            vec!["BUILDER_FLAGS", "RUST_VERSION"]
+        );
+        assert_eq!(
+            settings.module_template_sources()[0].location(),
+            "https://github.com/cyberfabric/cf-template-rust#stable"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_settings_keys() {
+        let raw = "[workspace.metadata.cyberfabric.build]\nwatch = true\n";
+        assert!(ProjectSettings::parse_manifest(raw).is_err());
+    }
+
+    #[test]
+    fn expands_deploy_tag_placeholders() {
+        let raw = "[workspace.metadata.cyberfabric.deploy]\ntag = \"acme/{name}:{version}\"\n";
+        let settings = ProjectSettings::parse_manifest(raw).expect("settings should parse");
+
+        assert_eq!(
+            settings
+                .deploy_tag("server", || Ok("1.2.0".to_owned()))
+                .expect("tag expands"),
+            Some("acme/server:1.2.0".to_owned())
+        );
+
+        let raw = "[workspace.metadata.cyberfabric.deploy]\ntag = \"acme/{name}:{sha}\"\n";
+        let settings = ProjectSettings::parse_manifest(raw).expect("settings should parse");
+        assert!(
+            settings
+                .deploy_tag("server", || Ok("1.2.0".to_owned()))
+                .is_err()
+        );
+    }
+}
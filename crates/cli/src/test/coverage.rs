@@ -0,0 +1,192 @@
+use super::{add_test_selection, module_roots, owning_module};
+use crate::common::{self, cargo_cmd};
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+/// Subset of the `cargo llvm-cov --json --summary-only` export we rely on.
+#[derive(Deserialize)]
+struct LlvmCovExport {
+    data: Vec<LlvmCovData>,
+}
+
+#[derive(Deserialize)]
+struct LlvmCovData {
+    files: Vec<LlvmCovFile>,
+}
+
+#[derive(Deserialize)]
+struct LlvmCovFile {
+    filename: PathBuf,
+    summary: LlvmCovSummary,
+}
+
+#[derive(Deserialize)]
+struct LlvmCovSummary {
+    lines: LlvmCovCount,
+}
+
+#[derive(Deserialize)]
+struct LlvmCovCount {
+    count: u64,
+    covered: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct LineCoverage {
+    pub covered: u64,
+    pub total: u64,
+}
+
+impl LineCoverage {
+    #[allow(clippy::cast_precision_loss)]
+    fn percent(self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        self.covered as f64 * 100.0 / self.total as f64
+    }
+}
+
+/// Line coverage keyed by modkit module name. Files that don't belong to any
+/// module are grouped under [`OTHER_LABEL`].
+pub(super) type CoverageReport = BTreeMap<String, LineCoverage>;
+
+const OTHER_LABEL: &str = "(other)";
+
+pub(super) fn run(
+    package: Option<&str>,
+    e2e: bool,
+    config_path: Option<&Path>,
+) -> anyhow::Result<CoverageReport> {
+    ensure_llvm_cov_installed()?;
+
+    let out_dir = tempfile::tempdir().context("could not create temp dir for coverage output")?;
+    let out_file = out_dir.path().join("coverage.json");
+
+    let mut cmd = cargo_cmd()?;
+    cmd.arg("llvm-cov");
+    add_test_selection(&mut cmd, package, e2e);
+    cmd.arg("--json")
+        .arg("--summary-only")
+        .arg("--output-path")
+        .arg(&out_file);
+    if let Some(config_path) = config_path {
+        cmd.env(common::CONFIG_PATH_ENV_VAR, config_path);
+    }
+
+    let status = cmd.status().context("failed to run cargo llvm-cov")?;
+    if !status.success() {
+        bail!("cargo llvm-cov exited with {status}");
+    }
+
+    let raw = fs::read_to_string(&out_file)
+        .with_context(|| format!("can't read coverage output {}", out_file.display()))?;
+    let roots = module_roots()?;
+    summarize(&raw, &roots)
+}
+
+fn ensure_llvm_cov_installed() -> anyhow::Result<()> {
+    let installed = cargo_cmd()?
+        .arg("llvm-cov")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    if installed {
+        return Ok(());
+    }
+    bail!(
+        "`cargo llvm-cov` is required for --coverage. Install it with `cargo install cargo-llvm-cov`"
+    )
+}
+
+fn summarize(raw: &str, roots: &[(String, PathBuf)]) -> anyhow::Result<CoverageReport> {
+    let export: LlvmCovExport =
+        serde_json::from_str(raw).context("invalid cargo llvm-cov JSON output")?;
+
+    let mut report = CoverageReport::new();
+    for file in export.data.into_iter().flat_map(|data| data.files) {
+        let module = owning_module(roots, &file.filename).unwrap_or(OTHER_LABEL);
+        let entry = report.entry(module.to_owned()).or_default();
+        entry.covered += file.summary.lines.covered;
+        entry.total += file.summary.lines.count;
+    }
+    Ok(report)
+}
+
+pub(super) fn print_summary(report: &CoverageReport) {
+    let width = report
+        .keys()
+        .map(String::len)
+        .chain(std::iter::once("module".len()))
+        .max()
+        .unwrap_or_default();
+
+    println!("{:<width$}  {:>13}  {:>7}", "module", "lines", "cover");
+    let mut total = LineCoverage::default();
+    for (module, coverage) in report {
+        total.covered += coverage.covered;
+        total.total += coverage.total;
+        print_row(module, *coverage, width);
+    }
+    print_row("total", total, width);
+}
+
+fn print_row(label: &str, coverage: LineCoverage, width: usize) {
+    println!(
+        "{label:<width$}  {:>13}  {:>6.2}%",
+        format!("{}/{}", coverage.covered, coverage.total),
+        coverage.percent()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineCoverage, summarize};
+    use std::path::PathBuf;
+
+    #[test]
+    fn summarize_groups_files_by_module() {
+        let raw = r#"{
+            "data": [{
+                "files": [
+                    {"filename": "/ws/modules/demo/src/lib.rs", "summary": {"lines": {"count": 10, "covered": 5, "percent": 50.0}}},
+                    {"filename": "/ws/modules/demo/src/module.rs", "summary": {"lines": {"count": 10, "covered": 10, "percent": 100.0}}},
+                    {"filename": "/ws/tools/helper.rs", "summary": {"lines": {"count": 4, "covered": 1, "percent": 25.0}}}
+                ],
+                "totals": {}
+            }],
+            "type": "llvm.coverage.json.export",
+            "version": "2.0.1"
+        }"#;
+        let roots = vec![("demo".to_owned(), PathBuf::from("/ws/modules/demo"))];
+
+        let report = summarize(raw, &roots).expect("coverage json should parse");
+
+        assert_eq!(
+            report.get("demo"),
+            Some(&LineCoverage {
+                covered: 15,
+                total: 20
+            })
+        );
+        assert_eq!(
+            report.get("(other)"),
+            Some(&LineCoverage {
+                covered: 1,
+                total: 4
+            })
+        );
+    }
+
+    #[test]
+    fn empty_coverage_is_reported_as_full() {
+        let coverage = LineCoverage::default();
+        assert!((coverage.percent() - 100.0).abs() < f64::EPSILON);
+    }
+}
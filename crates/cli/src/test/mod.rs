@@ -1,17 +1,315 @@
+mod coverage;
+
+use crate::app_config::AppConfig;
+use crate::common::{self, cargo_cmd, parse_and_chdir};
+use crate::run;
+use anyhow::{Context, bail};
 use clap::Args;
+use module_parser::{Capability, get_module_name_from_crate};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// Glob passed to `cargo test --test` to select the workspace's e2e test targets.
+const E2E_TEST_TARGETS: &str = "e2e*";
+/// How often the e2e server is checked for accepting connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 #[derive(Args)]
 pub struct TestArgs {
-    #[arg(long)]
+    /// Path to the module workspace root
+    #[arg(short = 'p', long, value_parser = parse_and_chdir)]
+    pub path: Option<PathBuf>,
+    /// Build and start the generated server, then run the `e2e*` test targets against it
+    #[arg(long, requires = "config")]
     e2e: bool,
+    /// Only run the tests of the given modkit module
     #[arg(long)]
     module: Option<String>,
+    /// Collect coverage with `cargo llvm-cov` and print a per-module summary
     #[arg(long)]
     coverage: bool,
+    /// Path to the config file used to generate the server for `--e2e`
+    #[arg(short = 'c', long)]
+    config: Option<PathBuf>,
+    /// Override the generated server and binary name for `--e2e`
+    #[arg(long)]
+    name: Option<String>,
+    /// Build and run the e2e server in release mode
+    #[arg(short = 'r', long)]
+    release: bool,
+    /// Seconds to wait for the e2e server to accept connections before giving up
+    #[arg(
+        long,
+        alias = "startup-delay",
+        default_value_t = 60,
+        value_name = "SECONDS"
+    )]
+    startup_timeout: u64,
 }
 
 impl TestArgs {
     pub fn run(&self) -> anyhow::Result<()> {
-        unimplemented!("Not implemented yet")
+        let package = self
+            .module
+            .as_deref()
+            .map(resolve_module_package)
+            .transpose()?;
+
+        let server = if self.e2e {
+            Some(self.start_e2e_server()?)
+        } else {
+            None
+        };
+
+        let result = self.run_tests(package.as_deref(), server.as_ref());
+
+        if let Some(server) = server {
+            run::stop_server(server.child);
+        }
+
+        result
+    }
+
+    fn run_tests(&self, package: Option<&str>, server: Option<&E2eServer>) -> anyhow::Result<()> {
+        if self.coverage {
+            let config_path = server.map(|server| server.config_path.as_path());
+            let report = coverage::run(package, self.e2e, config_path)?;
+            coverage::print_summary(&report);
+            return Ok(());
+        }
+
+        let mut cmd = cargo_cmd()?;
+        cmd.arg("test");
+        add_test_selection(&mut cmd, package, self.e2e);
+        if let Some(server) = server {
+            cmd.env(common::CONFIG_PATH_ENV_VAR, &server.config_path);
+        }
+
+        let status = cmd.status().context("failed to run cargo test")?;
+        if !status.success() {
+            bail!("cargo test exited with {status}");
+        }
+        Ok(())
+    }
+
+    fn start_e2e_server(&self) -> anyhow::Result<E2eServer> {
+        let config = self
+            .config
+            .as_ref()
+            .context("--e2e requires a config file, use -c")?;
+        let config_path = config.canonicalize().context("can't canonicalize config")?;
+        let project_name =
+            common::resolve_generated_project_name(&config_path, self.name.as_deref())?;
+
+        let dependencies = common::get_config(&config_path)?.create_dependencies(config)?;
+        common::generate_server_structure(&project_name, &dependencies)?;
+        let cargo_dir = common::generated_project_dir(&project_name)?;
+
+        let child = run::start_server(&cargo_dir, &config_path, self.release)
+            .context("failed to start the e2e server")?;
+        let mut server = E2eServer { child, config_path };
+
+        let Some(address) = server_address(&common::get_config(&server.config_path)?) else {
+            eprintln!(
+                "note: the config has no rest_host or grpc_hub port to wait for, starting the tests right away"
+            );
+            return Ok(server);
+        };
+        if let Err(err) = server.wait_until_listening(&address, self.startup_timeout) {
+            run::stop_server(server.child);
+            return Err(err);
+        }
+        Ok(server)
+    }
+}
+
+struct E2eServer {
+    child: Child,
+    config_path: PathBuf,
+}
+
+impl E2eServer {
+    /// Polls `address` until the server accepts connections, failing when the
+    /// server exits or `timeout` seconds pass first.
+    fn wait_until_listening(&mut self, address: &str, timeout: u64) -> anyhow::Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+        loop {
+            if let Some(status) = self
+                .child
+                .try_wait()
+                .context("can't check e2e server status")?
+            {
+                bail!("e2e server exited before the tests started with {status}");
+            }
+            let connected = address.to_socket_addrs().is_ok_and(|mut addrs| {
+                addrs.any(|addr| TcpStream::connect_timeout(&addr, POLL_INTERVAL).is_ok())
+            });
+            if connected {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                bail!("e2e server didn't accept connections on {address} within {timeout}s");
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// The address of the server's `rest_host` or, failing that, `grpc_hub`
+/// provider, reached through loopback when it listens on every interface.
+fn server_address(config: &AppConfig) -> Option<String> {
+    [Capability::RestHost, Capability::GrpcHub]
+        .iter()
+        .find_map(|capability| {
+            config
+                .providers(capability)
+                .find_map(|(_, module)| module.listen_address())
+        })
+        .map(|address| {
+            let host = match address.host {
+                None | Some("" | "0.0.0.0") => "127.0.0.1",
+                Some("[::]") => "[::1]",
+                Some(host) => host,
+            };
+            format!("{host}:{}", address.port)
+        })
+}
+
+fn add_test_selection(cmd: &mut Command, package: Option<&str>, e2e: bool) {
+    if let Some(package) = package {
+        cmd.arg("-p").arg(package);
+    } else {
+        cmd.arg("--workspace");
+    }
+    if e2e {
+        cmd.arg("--test").arg(E2E_TEST_TARGETS);
+    }
+}
+
+/// Maps a modkit module name (as declared in `#[modkit::module(name = ...)]`)
+/// to the Cargo package that defines it.
+fn resolve_module_package(module: &str) -> anyhow::Result<String> {
+    let modules = get_module_name_from_crate()?;
+    let Some(config_module) = modules.get(module) else {
+        let mut known: Vec<_> = modules.keys().map(String::as_str).collect();
+        known.sort_unstable();
+        bail!(
+            "module '{module}' not found in the workspace. Known modules: {}",
+            known.join(", ")
+        );
+    };
+    config_module
+        .metadata
+        .package
+        .clone()
+        .with_context(|| format!("module '{module}' has no package associated"))
+}
+
+fn module_roots() -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut roots: Vec<_> = get_module_name_from_crate()?
+        .into_iter()
+        .filter_map(|(name, module)| module.metadata.path.map(|p| (name, PathBuf::from(p))))
+        .collect();
+    roots.sort_by(|(left, _), (right, _)| left.cmp(right));
+    Ok(roots)
+}
+
+fn owning_module<'a>(roots: &'a [(String, PathBuf)], file: &Path) -> Option<&'a str> {
+    // Prefer the deepest root so nested crates (e.g. `<module>/sdk`) don't
+    // get attributed to their parent module.
+    roots
+        .iter()
+        .filter(|(_, root)| file.starts_with(root))
+        .max_by_key(|(_, root)| root.components().count())
+        .map(|(name, _)| name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TestArgs, add_test_selection, owning_module, server_address};
+    use crate::app_config::AppConfig;
+    use clap::Parser;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        test: TestArgs,
+    }
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn e2e_requires_config() {
+        assert!(TestCli::try_parse_from(["cyberfabric", "--e2e"]).is_err());
+        assert!(TestCli::try_parse_from(["cyberfabric", "--e2e", "-c", "config.yml"]).is_ok());
+    }
+
+    #[test]
+    fn server_address_reaches_wildcard_binds_through_loopback() {
+        let config = |modules: &str| -> AppConfig {
+            serde_saphyr::from_str(&format!("server:\n  home_dir: ~/.cyberfabric\n{modules}"))
+                .expect("config")
+        };
+
+        assert_eq!(
+            server_address(&config(
+                "modules:\n  api-gateway:\n    config:\n      bind_addr: 0.0.0.0:8087\n"
+            )),
+            Some("127.0.0.1:8087".to_owned())
+        );
+        assert_eq!(
+            server_address(&config(
+                "modules:\n  grpc-hub:\n    config:\n      listen_addr: \"[::]:50051\"\n"
+            )),
+            Some("[::1]:50051".to_owned())
+        );
+        assert_eq!(
+            server_address(&config("modules:\n  users:\n    config: {}\n")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_selection_defaults_to_workspace() {
+        let mut cmd = Command::new("cargo");
+        add_test_selection(&mut cmd, None, false);
+        assert_eq!(args(&cmd), vec!["--workspace"]);
+    }
+
+    #[test]
+    fn test_selection_limits_to_package_and_e2e_targets() {
+        let mut cmd = Command::new("cargo");
+        add_test_selection(&mut cmd, Some("cf-demo"), true);
+        assert_eq!(args(&cmd), vec!["-p", "cf-demo", "--test", "e2e*"]);
+    }
+
+    #[test]
+    fn owning_module_prefers_deepest_root() {
+        let roots = vec![
+            ("demo".to_owned(), PathBuf::from("/ws/modules/demo")),
+            ("demo-sdk".to_owned(), PathBuf::from("/ws/modules/demo/sdk")),
+        ];
+
+        assert_eq!(
+            owning_module(&roots, Path::new("/ws/modules/demo/src/lib.rs")),
+            Some("demo")
+        );
+        assert_eq!(
+            owning_module(&roots, Path::new("/ws/modules/demo/sdk/src/lib.rs")),
+            Some("demo-sdk")
+        );
+        assert_eq!(
+            owning_module(&roots, Path::new("/ws/other/src/lib.rs")),
+            None
+        );
     }
 }
@@ -1,6 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
+use std::path::PathBuf;
+
+/// JSON Schema of a module's `config` bag, shipped next to its `module.rs`.
+pub const CONFIG_SCHEMA_FILE: &str = "config.schema.json";
 
 #[derive(Deserialize)]
 pub struct Config {
@@ -10,9 +15,12 @@ pub struct Config {
 #[derive(Deserialize)]
 pub struct ConfigModule {
     pub metadata: ConfigModuleMetadata,
+    /// The module's [`CONFIG_SCHEMA_FILE`], when it ships one.
+    #[serde(skip)]
+    pub config_schema: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Capability {
     Db,
@@ -39,7 +47,7 @@ impl fmt::Display for Capability {
     }
 }
 
-#[derive(Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 pub struct ConfigModuleMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub package: Option<String>,
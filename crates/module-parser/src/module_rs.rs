@@ -1,4 +1,4 @@
-use super::config::{Capability, ConfigModule, ConfigModuleMetadata};
+use super::config::{CONFIG_SCHEMA_FILE, Capability, ConfigModule, ConfigModuleMetadata};
 use anyhow::Context;
 use cargo_metadata::{Package, Target};
 use std::fs;
@@ -26,6 +26,7 @@ pub fn retrieve_module_rs(
         .with_context(|| format!("can't read module from {}", module_rs.display()))?;
     let parsed_module = parse_module_rs_source(&content)
         .with_context(|| format!("invalid {}", module_rs.display()))?;
+    let config_schema = Some(src.join(CONFIG_SCHEMA_FILE)).filter(|path| path.is_file());
     let crate_root = PathBuf::from(&package.manifest_path)
         .parent()
         .map(|p| p.display().to_string());
@@ -40,6 +41,7 @@ pub fn retrieve_module_rs(
             deps: parsed_module.deps,
             capabilities: parsed_module.capabilities,
         },
+        config_schema,
     };
     Ok((parsed_module.name, config_module))
 }